@@ -1,4 +1,17 @@
 const API_URL_BASE = 'http://localhost:8080';
+const MARKER_GENERATIONS = 50;
+const WORLD_SIZE = 1024;
+const CELL_SIZE = 4;
+const FRAME_KEYFRAME = 1;
+const FRAME_DELTA = 2;
+
+let events = [];
+let last_event_id = 0;
+let show_markers = true;
+let socket = null;
+let cells = null;
+let cells_w = 0;
+let cells_h = 0;
 
 const get_x = () => {
     return Number(document.getElementById('x').value);
@@ -30,13 +43,156 @@ const get_d = () => {
 const set_d = (vd) => {
     return document.getElementById('d').value = vd;
 };
+const get_viewport = () => {
+    return {
+        x: Math.trunc(get_x() - get_w() / 2),
+        y: Math.trunc(get_y() - get_h() / 2),
+        w: get_w(),
+        h: get_h(),
+    };
+};
+const get_view = () => {
+    return document.getElementById(socket === null ? 'i' : 'c');
+};
+const draw_cells = () => {
+    let canvas = document.getElementById('c');
+    if (canvas.width !== cells_w || canvas.height !== cells_h) {
+        canvas.width = cells_w;
+        canvas.height = cells_h;
+        canvas.style.width = (cells_w * CELL_SIZE) + 'px';
+    };
+    let context = canvas.getContext('2d');
+    let image = context.createImageData(cells_w, cells_h);
+    for (let i = 0; i < cells.length; i++) {
+        let shade = cells[i] ? 0 : 255;
+        image.data[i * 4] = shade;
+        image.data[i * 4 + 1] = shade;
+        image.data[i * 4 + 2] = shade;
+        image.data[i * 4 + 3] = 255;
+    };
+    context.putImageData(image, 0, 0);
+};
+const frame_handler = (buffer) => {
+    // Frame layout is documented in game_stream.rs
+    let view = new DataView(buffer);
+    let kind = view.getUint8(0);
+    if (kind === FRAME_KEYFRAME) {
+        cells_w = view.getUint16(17, true);
+        cells_h = view.getUint16(19, true);
+        cells = new Uint8Array(cells_w * cells_h);
+        for (let i = 0; i < cells.length; i++) {
+            cells[i] = (view.getUint8(21 + (i >> 3)) >> (i & 7)) & 1;
+        };
+    } else if (kind === FRAME_DELTA && cells !== null) {
+        let born = view.getUint32(9, true);
+        let died = view.getUint32(13, true);
+        for (let i = 0; i < born + died; i++) {
+            let cx = view.getUint16(17 + i * 4, true);
+            let cy = view.getUint16(19 + i * 4, true);
+            cells[cy * cells_w + cx] = i < born ? 1 : 0;
+        };
+    } else {
+        return;
+    };
+    draw_cells();
+};
+const open_socket = () => {
+    let ws = new WebSocket(API_URL_BASE.replace(/^http/, 'ws') + '/ws');
+    ws.binaryType = 'arraybuffer';
+    ws.onopen = () => {
+        socket = ws;
+        document.getElementById('i').style.display = 'none';
+        document.getElementById('c').style.display = 'block';
+        send_viewport();
+    };
+    ws.onmessage = (message) => {
+        if (typeof message.data === 'string') {
+            console.log(message.data);
+        } else {
+            frame_handler(message.data);
+        };
+    };
+    ws.onclose = () => {
+        // Back to polling /getsvg
+        socket = null;
+        document.getElementById('c').style.display = 'none';
+        document.getElementById('i').style.display = 'block';
+    };
+};
+const send_viewport = () => {
+    if (socket !== null) {
+        socket.send(JSON.stringify(get_viewport()));
+    };
+};
 const get_image_api = () => {
     let lx = Math.trunc(get_x() - get_w() / 2);
     let ly = Math.trunc(get_y() - get_h() / 2);
     document.getElementById('i').src = API_URL_BASE + '/getsvg?x=' + lx + '&y=' + ly + '&w=' + get_w() + '&h=' + get_h() + '&r=' + Math.random();
 };
+const set_cell_api = (cx, cy, state) => {
+    fetch(API_URL_BASE + '/cells', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ cells: [{ x: cx, y: cy, state: state }] }),
+    }).then(() => {
+        if (socket === null) {
+            get_image_api();
+        };
+    });
+};
+const click_handler = (event) => {
+    let img = get_view();
+    let lx = Math.trunc(get_x() - get_w() / 2);
+    let ly = Math.trunc(get_y() - get_h() / 2);
+    let cx = lx + Math.floor(event.offsetX * get_w() / img.clientWidth);
+    let cy = ly + Math.floor(event.offsetY * get_h() / img.clientHeight);
+    set_cell_api(cx, cy, event.shiftKey ? 0 : 1);
+};
+const get_events_api = () => {
+    fetch(API_URL_BASE + '/events?since=' + last_event_id)
+        .then((response) => response.json())
+        .then((data) => {
+            data.events.forEach((event) => last_event_id = Math.max(last_event_id, event.id));
+            events = events.concat(data.events).filter((event) => data.generation - event.generation < MARKER_GENERATIONS);
+            draw_markers();
+        })
+        .catch(() => {});
+};
+const draw_markers = () => {
+    let img = get_view();
+    let markers = document.getElementById('m');
+    markers.replaceChildren();
+    if (!show_markers || img.clientWidth === 0) {
+        return;
+    };
+    let lx = Math.trunc(get_x() - get_w() / 2);
+    let ly = Math.trunc(get_y() - get_h() / 2);
+    let sx = img.clientWidth / get_w();
+    let sy = img.clientHeight / get_h();
+    events.forEach((event) => {
+        // Injections may wrap around the torus, so map them into the viewport first
+        let ex = ((event.x - lx) % WORLD_SIZE + WORLD_SIZE) % WORLD_SIZE;
+        let ey = ((event.y - ly) % WORLD_SIZE + WORLD_SIZE) % WORLD_SIZE;
+        if (ex >= get_w() || ey >= get_h()) {
+            return;
+        };
+        let marker = document.createElement('div');
+        marker.className = 'marker';
+        marker.style.left = (img.offsetLeft + ex * sx) + 'px';
+        marker.style.top = (img.offsetTop + ey * sy) + 'px';
+        marker.style.width = Math.max(event.w * sx, 4) + 'px';
+        marker.style.height = Math.max(event.h * sy, 4) + 'px';
+        let label = document.createElement('span');
+        label.textContent = (event.name || 'soup') + ' @' + event.generation;
+        marker.appendChild(label);
+        markers.appendChild(marker);
+    });
+};
 const game_refresh = () => {
-    get_image_api();
+    if (socket === null) {
+        get_image_api();
+    };
+    get_events_api();
     setTimeout(game_refresh, get_d());
 };
 const key_handler = (event) => {
@@ -88,10 +244,17 @@ const key_handler = (event) => {
         set_w(get_w() + 10);
     } else if (event.key === 'E') {
         set_w(get_w() - 10);
+    } else if (event.key === 'm') {
+        show_markers = !show_markers;
     } else {
         return;
     };
-    get_image_api();
+    if (socket === null) {
+        get_image_api();
+    } else {
+        send_viewport();
+    };
+    draw_markers();
 };
 const main = () => {
     // Setup initial variable values
@@ -101,7 +264,12 @@ const main = () => {
     set_h(200);
     set_d(1000);
     // Register event handlers
-    window.addEventListener('load', () => game_refresh());
+    window.addEventListener('load', () => {
+        open_socket();
+        game_refresh();
+    });
     window.addEventListener('keydown', (e) => key_handler(e));
+    document.getElementById('i').addEventListener('click', (e) => click_handler(e));
+    document.getElementById('c').addEventListener('click', (e) => click_handler(e));
 };
 main();
\ No newline at end of file
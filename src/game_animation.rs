@@ -0,0 +1,344 @@
+use flate2::{write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::fs::write;
+use std::io::Write;
+use std::path::Path;
+use stopwatch::Stopwatch;
+
+use crate::game_classifier::{cells_from_plane, step_cells, CellSet};
+use crate::game_cli::AnimateArgs;
+use crate::game_constants::{
+    UniversePlane, ANIMATION_DEFAULT_FPS, ANIMATION_MAX_CELLS, ANIMATION_MAX_FPS,
+    ANIMATION_MAX_PIXELS, CELL_DEATH, CELL_LIVE, HISTORY_SIZE,
+};
+use crate::game_render::{
+    cell_values, get_depth, get_palette, pack_row, pixel_rows, set_png_palette, shade_plane,
+    RenderOptions, RenderQuery,
+};
+use crate::game_seeding::load_seed_file;
+use crate::game_stats::empty_as_none;
+use crate::game_universe::WorldBounds;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn get_content_type(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "image/gif",
+            AnimationFormat::Apng => "image/apng",
+        }
+    }
+
+    // .png and .apng are APNG, anything else a GIF
+    pub fn from_path(path: &Path) -> AnimationFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") | Some("apng") => AnimationFormat::Apng,
+            _ => AnimationFormat::Gif,
+        }
+    }
+}
+
+// Render options come from a RenderQuery read from the same query string
+#[derive(Deserialize, Debug)]
+pub struct AnimationQuery {
+    pub x: isize,
+    pub y: isize,
+    pub w: isize,
+    pub h: isize,
+    // Generations, the oldest and newest in the history by default
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub from: Option<usize>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub to: Option<usize>,
+    pub fps: Option<u16>,
+    pub format: Option<AnimationFormat>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationOptions {
+    pub fps: u16,
+    pub format: AnimationFormat,
+}
+
+impl AnimationQuery {
+    pub fn get_bounds(&self) -> WorldBounds {
+        WorldBounds {
+            x: self.x,
+            y: self.y,
+            w: self.w,
+            h: self.h,
+        }
+    }
+
+    pub fn get_options(&self) -> Result<AnimationOptions, String> {
+        get_animation_options(self.fps, self.format.unwrap_or_default())
+    }
+
+    // Most frames the request can cover, known before the history is read
+    pub fn get_frame_bound(&self) -> usize {
+        let history = HISTORY_SIZE as usize;
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if from <= to => (to - from).min(history - 1) + 1,
+            _ => history,
+        }
+    }
+
+    // Requested generations, checked against what the history still holds
+    pub fn get_range(&self, history: (usize, usize)) -> Result<(usize, usize), String> {
+        let (oldest, newest) = history;
+        let from = self.from.unwrap_or(oldest);
+        let to = self.to.unwrap_or(newest);
+
+        if from > to {
+            return Err(format!("from {} is after to {}", from, to));
+        }
+        if from < oldest || to > newest {
+            return Err(format!(
+                "Generations {} to {} are not in the history, it holds {} to {}",
+                from, to, oldest, newest
+            ));
+        }
+
+        Ok((from, to))
+    }
+}
+
+pub fn get_animation_options(fps: Option<u16>, format: AnimationFormat) -> Result<AnimationOptions, String> {
+    let fps = fps.unwrap_or(ANIMATION_DEFAULT_FPS);
+    if fps == 0 || fps > ANIMATION_MAX_FPS {
+        return Err(format!("fps must be between 1 and {}", ANIMATION_MAX_FPS));
+    }
+
+    Ok(AnimationOptions { fps, format })
+}
+
+pub fn check_animation_size(options: &RenderOptions, xsize: usize, ysize: usize, frames: usize) -> Result<(), String> {
+    let (width, height) = options.get_size(xsize, ysize);
+    if width * height * frames > ANIMATION_MAX_PIXELS {
+        return Err(format!(
+            "{} frames of {}x{} are more than {} pixels, lower scale, use zoom_out or fewer generations",
+            frames, width, height, ANIMATION_MAX_PIXELS
+        ));
+    }
+
+    Ok(())
+}
+
+// Frames are copied under the universe lock, whatever scale they are drawn at
+pub fn check_animation_cells(xsize: usize, ysize: usize, frames: usize) -> Result<(), String> {
+    if xsize * ysize * frames > ANIMATION_MAX_CELLS {
+        return Err(format!(
+            "{} frames of {}x{} cells are more than {} cells, use a smaller region or fewer generations",
+            frames, xsize, ysize, ANIMATION_MAX_CELLS
+        ));
+    }
+
+    Ok(())
+}
+
+// Frames share the size of the first one and the palette of the render options
+pub fn render_animation(
+    frames: &[UniversePlane],
+    options: &RenderOptions,
+    animation: &AnimationOptions,
+) -> Result<Vec<u8>, String> {
+    if frames.is_empty() {
+        return Err("No frames".to_string());
+    }
+
+    let (width, height) = options.get_size(frames[0].len(), frames[0][0].len());
+    check_animation_size(options, frames[0].len(), frames[0][0].len(), frames.len())?;
+
+    match animation.format {
+        AnimationFormat::Gif => encode_gif(frames, options, animation, width, height),
+        AnimationFormat::Apng => encode_apng(frames, options, animation, width, height),
+    }
+}
+
+fn encode_gif(
+    frames: &[UniversePlane],
+    options: &RenderOptions,
+    animation: &AnimationOptions,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, String> {
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(format!("GIF frames are at most {} pixels wide", u16::MAX));
+    }
+
+    let palette: Vec<u8> = get_palette(options).iter().flat_map(|color| color.0).collect();
+
+    // GIF delays are in hundredths of a second
+    let delay = (100.0 / animation.fps as f32).round() as u16;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut buffer, width as u16, height as u16, &palette)
+            .map_err(|e| e.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+        for world in frames {
+            let mut pixels: Vec<u8> = Vec::with_capacity(width * height);
+            pixel_rows(&shade_plane(&cell_values(world), options.zoom_out), options, width, |row, repeat| {
+                for _ in 0..repeat {
+                    pixels.extend_from_slice(row);
+                }
+                Ok(())
+            })?;
+
+            let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+            frame.delay = delay;
+
+            encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(buffer)
+}
+
+// The animation chunks are written by hand, png barely compresses write_image_data frames and its stream writer misplaces fdAT chunks
+fn encode_apng(
+    frames: &[UniversePlane],
+    options: &RenderOptions,
+    animation: &AnimationOptions,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, String> {
+    let palette = get_palette(options);
+    let depth = get_depth(&palette);
+
+    let mut buffer: Vec<u8> = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, width as u32, height as u32);
+        set_png_palette(&mut encoder, &palette);
+
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+
+        // Frame count and zero plays to loop forever
+        let mut actl: Vec<u8> = Vec::with_capacity(8);
+        actl.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+        actl.extend_from_slice(&0u32.to_be_bytes());
+        writer.write_chunk(png::chunk::acTL, &actl).map_err(|e| e.to_string())?;
+
+        // fcTL and fdAT chunks share one sequence
+        let mut sequence: u32 = 0;
+
+        for (index, world) in frames.iter().enumerate() {
+            let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+            let mut packed: Vec<u8> = Vec::new();
+            pixel_rows(&shade_plane(&cell_values(world), options.zoom_out), options, width, |row, repeat| {
+                pack_row(row, depth, &mut packed);
+                for _ in 0..repeat {
+                    // No filter in front of every row
+                    zlib.write_all(&[0]).map_err(|e| e.to_string())?;
+                    zlib.write_all(&packed).map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            })?;
+            let data = zlib.finish().map_err(|e| e.to_string())?;
+
+            // Full frame at the origin, delay fps, no disposal and no blending
+            let mut fctl: Vec<u8> = Vec::with_capacity(26);
+            fctl.extend_from_slice(&sequence.to_be_bytes());
+            fctl.extend_from_slice(&(width as u32).to_be_bytes());
+            fctl.extend_from_slice(&(height as u32).to_be_bytes());
+            fctl.extend_from_slice(&0u32.to_be_bytes());
+            fctl.extend_from_slice(&0u32.to_be_bytes());
+            fctl.extend_from_slice(&1u16.to_be_bytes());
+            fctl.extend_from_slice(&animation.fps.to_be_bytes());
+            fctl.extend_from_slice(&[0, 0]);
+            writer.write_chunk(png::chunk::fcTL, &fctl).map_err(|e| e.to_string())?;
+            sequence += 1;
+
+            // The first frame is also the still image
+            match index {
+                0 => writer.write_chunk(png::chunk::IDAT, &data).map_err(|e| e.to_string())?,
+                _ => {
+                    let mut fdat: Vec<u8> = Vec::with_capacity(4 + data.len());
+                    fdat.extend_from_slice(&sequence.to_be_bytes());
+                    fdat.extend_from_slice(&data);
+                    writer.write_chunk(png::chunk::fdAT, &fdat).map_err(|e| e.to_string())?;
+                    sequence += 1;
+                },
+            }
+        }
+
+        writer.finish().map_err(|e| e.to_string())?;
+    }
+
+    Ok(buffer)
+}
+
+// Runs the pattern on an unbounded plane, every frame shows the box all generations fit in
+fn simulate_pattern(entity: &UniversePlane, generations: usize, margin: isize) -> Vec<UniversePlane> {
+    let mut steps: Vec<CellSet> = vec![cells_from_plane(entity)];
+    for _ in 0..generations {
+        let next = step_cells(&steps[steps.len() - 1]);
+        steps.push(next);
+    }
+
+    let all = || steps.iter().flat_map(|cells| cells.iter());
+    let min_x = all().map(|(x, _)| *x).min().unwrap_or(0) - margin;
+    let max_x = all().map(|(x, _)| *x).max().unwrap_or(0) + margin;
+    let min_y = all().map(|(_, y)| *y).min().unwrap_or(0) - margin;
+    let max_y = all().map(|(_, y)| *y).max().unwrap_or(0) + margin;
+
+    steps
+        .iter()
+        .map(|cells| {
+            let mut world: UniversePlane =
+                vec![vec![CELL_DEATH; (max_y - min_y + 1) as usize]; (max_x - min_x + 1) as usize];
+            for (x, y) in cells {
+                world[(x - min_x) as usize][(y - min_y) as usize] = CELL_LIVE;
+            }
+            world
+        })
+        .collect()
+}
+
+pub fn animate_main(args: &AnimateArgs) -> std::io::Result<()> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+
+    let entity = load_seed_file(&args.pattern).map_err(invalid)?;
+
+    let frames = simulate_pattern(&entity, args.generations, args.margin);
+
+    let query = RenderQuery {
+        scale: Some(args.scale),
+        theme: args.theme,
+        grid: Some(args.grid),
+        ..RenderQuery::default()
+    };
+    let options = query
+        .get_options(frames[0].len() as isize, frames[0][0].len() as isize)
+        .map_err(invalid)?;
+
+    let format = args.format.unwrap_or(AnimationFormat::from_path(&args.output));
+    let animation = get_animation_options(Some(args.fps), format).map_err(invalid)?;
+
+    let buffer = render_animation(&frames, &options, &animation).map_err(invalid)?;
+
+    write(&args.output, &buffer)?;
+
+    sw.stop();
+
+    println!(
+        "animate_main() {} frames of {}x{} cells, {} bytes to {}, elapsed [{} ms]",
+        frames.len(),
+        frames[0].len(),
+        frames[0][0].len(),
+        buffer.len(),
+        args.output.display(),
+        sw.elapsed_ms()
+    );
+
+    Ok(())
+}
@@ -1,31 +1,167 @@
-use actix_web::{get, http::header::ContentType, web, HttpResponse, Responder};
-use image::ImageBuffer;
-use imageproc::{drawing, rect::Rect};
-use std::io::Write;
+use actix_web::{
+    get,
+    http::header::{self, ContentType, Header},
+    post, put, web, HttpRequest, HttpResponse,
+};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
 use stopwatch::Stopwatch;
+use tokio::sync::broadcast::Sender;
+
+use crate::game_animation::{
+    check_animation_cells, check_animation_size, render_animation, AnimationQuery,
+};
+use crate::game_census::take_census;
+use crate::game_constants::{
+    CELLS_MAX_EDITS, CELL_DEATH, CELL_LIVE, ENGINE_MAX_DELAY_MILLIS, ENGINE_MIN_DELAY_MILLIS,
+    MIN_X, MIN_Y, RENDER_HEAT_MAX_CELL_STEPS, WORLD_SIZE_X, WORLD_SIZE_Y, UniversePlane,
+    UniverseCell,
+};
+use crate::game_error::ApiError;
+use crate::game_entropy::{EntropyPolicy, InjectionReport};
+use crate::game_period::WorldCycle;
+use crate::game_render::{
+    age_values, cell_values, heat_values, render_image, render_svg, ImageQuery, RenderMode, RenderPlane,
+    RenderQuery,
+};
+use crate::game_region::{region_binary, region_bitmap, region_cells, RegionLayout, RegionQuery};
+use crate::game_seeding::{seed_world, WorldSeeding};
+use crate::game_species::{
+    species_rle_to_vec, species_transform, Species, SpeciesMetadata, SpeciesQuery, SpeciesSymmetry,
+    SpeciesTransform,
+};
+use crate::game_engine::EngineControl;
+use crate::game_stream::{event_stream, viewport_session, EngineEvent};
+use crate::game_stats::{empty_as_none, stats_to_csv, GenerationStats, StatsFormat, StatsQuery};
+use crate::game_tiles::{tile_etag, TilePath};
+use crate::game_universe::{
+    check_position, BoundsLimits, Placement, PlacementMode, Universe, WorldBounds,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct PlaceRequest {
+    pub species: Option<usize>,
+    pub rle: Option<String>,
+    pub x: isize,
+    pub y: isize,
+    #[serde(default)]
+    pub transform: SpeciesTransform,
+    #[serde(default)]
+    pub mode: PlacementMode,
+}
 
-use crate::game_constants::{CELL_LIVE, CELL_SIZE, UniversePlane};
-use crate::game_universe::{Universe, WorldBounds};
+#[derive(Serialize, Debug)]
+pub struct PlaceResponse {
+    pub generation: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SpeciesEntry<'a> {
+    pub id: usize,
+    pub name: &'a str,
+    pub symmetry: SpeciesSymmetry,
+    pub orientations: usize,
+    #[serde(flatten)]
+    pub metadata: &'a SpeciesMetadata,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatusResponse {
+    pub seed: u64,
+    pub generation: usize,
+    pub population: Option<usize>,
+    pub species: usize,
+    // Present while the whole world repeats with a fixed period
+    pub cycle: Option<WorldCycle>,
+    pub last_injections: Vec<InjectionReport>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventsQuery {
+    // Only events with a larger id, for incremental polling
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub since: Option<usize>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub from: Option<usize>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub to: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EventsResponse {
+    pub generation: usize,
+    pub events: Vec<InjectionReport>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ControlRequest {
+    pub paused: Option<bool>,
+    pub delay_millis: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SeedRequest {
+    pub seed: Option<u64>,
+    // Replaces the configured seeding, kept for later restarts
+    pub seeding: Option<WorldSeeding>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CellEdit {
+    pub x: isize,
+    pub y: isize,
+    pub state: UniverseCell,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RectOp {
+    Fill,
+    Clear,
+    Randomize,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RectEdit {
+    pub x: isize,
+    pub y: isize,
+    pub w: isize,
+    pub h: isize,
+    pub op: RectOp,
+    pub density: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CellsRequest {
+    #[serde(default)]
+    pub cells: Vec<CellEdit>,
+    #[serde(default)]
+    pub rects: Vec<RectEdit>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CellsResponse {
+    pub generation: usize,
+    pub cells: usize,
+}
 
 #[get("/gettext")]
 pub async fn gettext(
     bounds: web::Query<WorldBounds>,
+    limits: web::Data<BoundsLimits>,
     rwlock_app: web::Data<Arc<RwLock<Universe>>>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let mut sw: Stopwatch = Stopwatch::start_new();
 
+    bounds.validate(&limits).map_err(ApiError::BadRequest)?;
+
     let _current_time: isize;
 
     let world: UniversePlane;
 
     {
-        let unlocked_data = match rwlock_app.read() {
-            Err(error) => {
-                return HttpResponse::InternalServerError().body(format!("{:?}", error));
-            },
-            Ok(data) => data
-        };
+        let unlocked_data = rwlock_app.read()?;
 
         let universe = &*unlocked_data;
 
@@ -49,168 +185,770 @@ pub async fn gettext(
 
     println!("gettext() elapsed [{} ms]", sw.elapsed_ms());
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::plaintext())
-        .body(text_canvas)
+        .body(text_canvas))
 }
 
 #[get("/getsvg")]
 pub async fn getsvg(
     bounds: web::Query<WorldBounds>,
+    render: web::Query<RenderQuery>,
+    limits: web::Data<BoundsLimits>,
     rwlock_app: web::Data<Arc<RwLock<Universe>>>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let mut sw: Stopwatch = Stopwatch::start_new();
 
+    bounds.validate(&limits).map_err(ApiError::BadRequest)?;
+
+    let options = render.get_options(bounds.w, bounds.h).map_err(ApiError::BadRequest)?;
+
     let _current_time: isize;
 
-    let world: UniversePlane;
+    let values: RenderPlane;
 
     {
-        let unlocked_data = match rwlock_app.read() {
-            Err(error) => {
-                return HttpResponse::InternalServerError().body(format!("{:?}", error));
-            },
-            Ok(data) => data
-        };
+        let unlocked_data = rwlock_app.read()?;
 
         let universe = &*unlocked_data;
 
         _current_time = universe.get_current_time();
 
-        world = universe.get_current_world(bounds.0);
-    }
-
-    let xsize = world.len();
-    let ysize = world[0].len();
-
-    let mut svgcontent = String::new();
-    svgcontent.push_str("<?xml version='1.0' encoding='UTF-8'?>");
-    svgcontent.push_str(
-        "<!DOCTYPE svg PUBLIC '-//W3C//DTD SVG 1.1//EN' 'http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd'>"
-    );
-    svgcontent.push_str(
-        format!(
-            "<svg xmlns='http://www.w3.org/2000/svg' version='1.1' width='{}' height='{}'>",
-            (xsize * CELL_SIZE) as u32,
-            (ysize * CELL_SIZE) as u32
-        )
-        .as_str(),
-    );
-    svgcontent.push_str(
-        format!(
-            "  <rect x='0' y='0' width='{}' height='{}' fill='white' />",
-            (xsize * CELL_SIZE) as u32,
-            (ysize * CELL_SIZE) as u32
-        )
-        .as_str(),
-    );
-
-    for a in 0..xsize {
-        for b in 0..ysize {
-            match world[a][b] {
-                CELL_LIVE => {
-                    svgcontent.push_str(
-                        format!(
-                            "  <rect x='{}' y='{}' width='{}' height='{}' fill='black' />",
-                            (a * CELL_SIZE) as i32,
-                            (b * CELL_SIZE) as i32,
-                            CELL_SIZE as u32,
-                            CELL_SIZE as u32
-                        )
-                        .as_str(),
-                    );
-                }
-                _ => {}
-            }
-        }
+        values = get_render_values(universe, bounds.0, options.mode)?;
     }
 
-    svgcontent.push_str("</svg>");
+    let svgcontent = render_svg(&values, &options);
 
     sw.stop();
 
     println!("getsvg() elapsed [{} ms]", sw.elapsed_ms());
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("image/svg+xml")
-        .body(svgcontent)
+        .body(svgcontent))
 }
 
 #[get("/getimage")]
 pub async fn getimage(
     bounds: web::Query<WorldBounds>,
+    render: web::Query<RenderQuery>,
+    image: web::Query<ImageQuery>,
+    limits: web::Data<BoundsLimits>,
     rwlock_app: web::Data<Arc<RwLock<Universe>>>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let mut sw: Stopwatch = Stopwatch::start_new();
 
+    bounds.validate(&limits).map_err(ApiError::BadRequest)?;
+
+    let options = render.get_options(bounds.w, bounds.h).map_err(ApiError::BadRequest)?;
+    let image = image.get_options().map_err(ApiError::BadRequest)?;
+
     let _current_time: isize;
 
-    let world: UniversePlane;
+    let values: RenderPlane;
 
     {
-        let unlocked_data = match rwlock_app.read() {
-            Err(error) => {
-                return HttpResponse::InternalServerError().body(format!("{:?}", error));
-            },
-            Ok(data) => data
-        };
+        let unlocked_data = rwlock_app.read()?;
 
         let universe = &*unlocked_data;
 
         _current_time = universe.get_current_time();
 
-        world = universe.get_current_world(bounds.0);
+        values = get_render_values(universe, bounds.0, options.mode)?;
     }
 
-    let xsize = world.len();
-    let ysize = world[0].len();
+    let buffer = render_image(&values, &options, &image).map_err(ApiError::Internal)?;
+
+    sw.stop();
 
-    let mut img = ImageBuffer::new((xsize * CELL_SIZE) as u32, (ysize * CELL_SIZE) as u32);
+    println!("getimage() elapsed [{} ms]", sw.elapsed_ms());
 
-    drawing::draw_filled_rect_mut(
-        &mut img,
-        Rect::at(0, 0).of_size((xsize * CELL_SIZE) as u32, (ysize * CELL_SIZE) as u32),
-        image::Rgb([255u8, 255u8, 255u8]),
-    );
+    Ok(HttpResponse::Ok()
+        .content_type(image.format.get_content_type())
+        .body(buffer))
+}
 
-    for a in 0..xsize {
-        for b in 0..ysize {
-            match world[a][b] {
-                CELL_LIVE => {
-                    drawing::draw_filled_rect_mut(
-                        &mut img,
-                        Rect::at((a * CELL_SIZE) as i32, (b * CELL_SIZE) as i32)
-                            .of_size(CELL_SIZE as u32, CELL_SIZE as u32),
-                        image::Rgb([0u8, 0u8, 0u8]),
-                    );
-                }
-                _ => {}
+#[get("/tiles/{z}/{x}/{y}.png")]
+pub async fn tile(
+    path: web::Path<TilePath>,
+    render: web::Query<RenderQuery>,
+    request: HttpRequest,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let tile = path.get_tile().map_err(ApiError::NotFound)?;
+
+    let query = tile.get_render(render.into_inner()).map_err(ApiError::BadRequest)?;
+    let options = query
+        .get_options(tile.bounds.w, tile.bounds.h)
+        .map_err(ApiError::BadRequest)?;
+
+    let generation: usize;
+    let values: RenderPlane;
+
+    {
+        let unlocked_data = rwlock_app.read()?;
+
+        generation = unlocked_data.get_generation();
+        values = get_render_values(&unlocked_data, tile.bounds, options.mode)?;
+    }
+
+    let etag = tile_etag(&options, &values);
+
+    // Viewers send back the tag of the tile they hold, unchanged tiles are not sent again
+    let cached = match request.headers().get(header::IF_NONE_MATCH).map(|value| value.to_str()) {
+        Some(Ok(value)) => value.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"),
+        _ => false,
+    };
+
+    let mut builder = match cached {
+        true => HttpResponse::NotModified(),
+        false => HttpResponse::Ok(),
+    };
+    builder
+        .insert_header((header::ETAG, etag.as_str()))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Generation", generation.to_string()));
+
+    let response = match cached {
+        true => builder.finish(),
+        false => {
+            let image = ImageQuery::default().get_options().map_err(ApiError::Internal)?;
+            let buffer = render_image(&values, &options, &image).map_err(ApiError::Internal)?;
+            builder.content_type(ContentType::png()).body(buffer)
+        },
+    };
+
+    sw.stop();
+
+    println!("tile() {}/{}/{} elapsed [{} ms]", path.z, path.x, path.y, sw.elapsed_ms());
+
+    Ok(response)
+}
+
+#[get("/animation")]
+pub async fn animation(
+    query: web::Query<AnimationQuery>,
+    render: web::Query<RenderQuery>,
+    limits: web::Data<BoundsLimits>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let bounds = query.get_bounds();
+    bounds.validate(&limits).map_err(ApiError::BadRequest)?;
+
+    let options = render.get_options(bounds.w, bounds.h).map_err(ApiError::BadRequest)?;
+    let animation = query.get_options().map_err(ApiError::BadRequest)?;
+
+    if options.mode != RenderMode::Cells {
+        return Err(ApiError::BadRequest("Animations only render mode=cells".to_string()));
+    }
+
+    check_animation_cells(bounds.w as usize, bounds.h as usize, query.get_frame_bound())
+        .map_err(ApiError::BadRequest)?;
+
+    let mut frames: Vec<UniversePlane> = Vec::new();
+
+    {
+        let unlocked_data = rwlock_app.read()?;
+
+        let universe = &*unlocked_data;
+
+        let (from, to) = query
+            .get_range(universe.get_history_range())
+            .map_err(ApiError::BadRequest)?;
+
+        check_animation_size(&options, bounds.w as usize, bounds.h as usize, to - from + 1)
+            .map_err(ApiError::BadRequest)?;
+
+        for generation in from..=to {
+            match universe.get_world_at(generation, bounds) {
+                None => {
+                    return Err(ApiError::Internal(format!("Generation {} left the history", generation)));
+                },
+                Some(world) => frames.push(world),
             }
         }
     }
 
-    let mut buffer: Vec<u8> = Vec::new();
-    {
-        let mut writer = std::io::Cursor::new(&mut buffer);
-        match img.write_to(&mut writer, image::ImageOutputFormat::Png) {
+    let frame_count = frames.len();
+
+    // Encoding can take seconds, so it runs off the async workers like /census
+    let buffer = match web::block(move || render_animation(&frames, &options, &animation)).await {
+        Err(error) => {
+            return Err(ApiError::Internal(error.to_string()));
+        },
+        Ok(buffer) => buffer.map_err(ApiError::Internal)?
+    };
+
+    sw.stop();
+
+    println!("animation() {} frames elapsed [{} ms]", frame_count, sw.elapsed_ms());
+
+    Ok(HttpResponse::Ok()
+        .content_type(animation.format.get_content_type())
+        .body(buffer))
+}
+
+#[post("/place")]
+pub async fn place(
+    request: web::Json<PlaceRequest>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    check_position(request.x, request.y).map_err(ApiError::BadRequest)?;
+
+    // Inline patterns are parsed and sized before any lock is taken
+    let entity_base = match (request.species, &request.rle) {
+        (Some(species_id), None) => {
+            let unlocked_data = rwlock_app.read()?;
+
+            match unlocked_data.get_species().get(species_id) {
+                None => {
+                    return Err(ApiError::NotFound(format!("Unknown species: {}", species_id)));
+                },
+                Some(species) => species.get_base().clone()
+            }
+        },
+        (None, Some(rle)) => match species_rle_to_vec(rle.as_str()) {
             Err(e) => {
-                return HttpResponse::InternalServerError().body(format!("{:?}", e));
+                return Err(ApiError::BadRequest(e));
             },
-            Ok(_) => {}
+            Ok(entity) => entity
+        },
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Exactly one of species or rle must be given".to_string(),
+            ));
         }
-        match writer.flush() {
-            Err(e) => {
-                return HttpResponse::InternalServerError().body(format!("{:?}", e));
+    };
+
+    if !entity_base.iter().flatten().any(|cell| *cell == CELL_LIVE) {
+        return Err(ApiError::BadRequest("Empty pattern".to_string()));
+    }
+
+    let entity = species_transform(&entity_base, request.transform);
+
+    let generation: usize;
+
+    {
+        let mut unlocked_data = rwlock_app.write()?;
+
+        let universe = &mut *unlocked_data;
+
+        universe.push_placement(Placement {
+            x_pos: request.x,
+            y_pos: request.y,
+            entity,
+            mode: request.mode,
+        });
+
+        // Pending placements are applied by the engine on the next generation
+        generation = universe.get_generation() + 1;
+    }
+
+    sw.stop();
+
+    println!("place() elapsed [{} ms]", sw.elapsed_ms());
+
+    Ok(HttpResponse::Ok().json(PlaceResponse { generation }))
+}
+
+#[post("/cells")]
+pub async fn cells(
+    request: web::Json<CellsRequest>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let mut requested_cells: usize = request.cells.len();
+
+    for cell in &request.cells {
+        if cell.state != CELL_DEATH && cell.state != CELL_LIVE {
+            return Err(ApiError::BadRequest(format!("Invalid cell state: {}", cell.state)));
+        }
+        check_position(cell.x, cell.y).map_err(ApiError::BadRequest)?;
+    }
+    for rect in &request.rects {
+        check_position(rect.x, rect.y).map_err(ApiError::BadRequest)?;
+        if rect.w < 0 || rect.h < 0 || rect.w > WORLD_SIZE_X || rect.h > WORLD_SIZE_Y {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid rect size: {} x {}",
+                rect.w, rect.h
+            )));
+        }
+        match rect.density {
+            Some(density) if !(0.0..=1.0).contains(&density) => {
+                return Err(ApiError::BadRequest(format!("Invalid density: {}", density)));
             },
-            Ok(_) => {}
+            _ => {}
+        }
+        requested_cells += (rect.w * rect.h) as usize;
+    }
+
+    // The whole batch runs under the write lock, one world of cells at most
+    if requested_cells > CELLS_MAX_EDITS {
+        return Err(ApiError::BadRequest(format!(
+            "{} cells edited, at most {} per request",
+            requested_cells, CELLS_MAX_EDITS
+        )));
+    }
+
+    let generation: usize;
+    let mut edited_cells: usize = 0;
+
+    {
+        let mut unlocked_data = rwlock_app.write()?;
+
+        let universe = &mut *unlocked_data;
+
+        let current_time = universe.get_current_time();
+
+        for cell in &request.cells {
+            universe.set_cell(current_time, cell.x, cell.y, 0, 0, cell.state);
+            edited_cells += 1;
+        }
+
+        for rect in &request.rects {
+            let density = rect.density.unwrap_or(0.5);
+            for i in 0..rect.w {
+                for j in 0..rect.h {
+                    let cell_state = match rect.op {
+                        RectOp::Fill => CELL_LIVE,
+                        RectOp::Clear => CELL_DEATH,
+                        RectOp::Randomize => match universe.get_rng().gen_bool(density) {
+                            true => CELL_LIVE,
+                            false => CELL_DEATH,
+                        },
+                    };
+                    universe.set_cell(current_time, rect.x, rect.y, i, j, cell_state);
+                    edited_cells += 1;
+                }
+            }
         }
+
+        universe.refresh_current();
+
+        // Edits land on the plane the engine reads next, so they are visible now
+        generation = universe.get_generation();
     }
 
     sw.stop();
 
-    println!("getimage() elapsed [{} ms]", sw.elapsed_ms());
+    println!("cells() elapsed [{} ms]", sw.elapsed_ms());
+
+    Ok(HttpResponse::Ok().json(CellsResponse {
+        generation,
+        cells: edited_cells,
+    }))
+}
+
+#[get("/species/report")]
+pub async fn species_report(rwlock_app: web::Data<Arc<RwLock<Universe>>>) -> Result<HttpResponse, ApiError> {
+    let unlocked_data = rwlock_app.read()?;
+
+    Ok(HttpResponse::Ok().json(unlocked_data.get_species_report()))
+}
+
+#[get("/species")]
+pub async fn species_index(
+    query: web::Query<SpeciesQuery>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let unlocked_data = rwlock_app.read()?;
+
+    let entries: Vec<SpeciesEntry> = unlocked_data
+        .get_species()
+        .iter()
+        .enumerate()
+        .filter(|(_, species)| query.matches(species))
+        .map(|(id, species)| SpeciesEntry {
+            id,
+            name: species.name.as_str(),
+            symmetry: species.symmetry,
+            orientations: species.orientations.len(),
+            metadata: &species.metadata,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[get("/census")]
+pub async fn census(rwlock_app: web::Data<Arc<RwLock<Universe>>>) -> Result<HttpResponse, ApiError> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let generation: usize;
+
+    let world: UniversePlane;
+
+    let species: Vec<Species>;
+
+    {
+        let unlocked_data = rwlock_app.read()?;
+
+        let universe = &*unlocked_data;
+
+        generation = universe.get_generation();
+
+        world = universe.get_current_world(WorldBounds {
+            x: MIN_X,
+            y: MIN_Y,
+            w: WORLD_SIZE_X,
+            h: WORLD_SIZE_Y,
+        });
+
+        species = universe.get_species().clone();
+    }
+
+    // Classification is slow, so it runs on the snapshot without holding the lock
+    let census = match web::block(move || take_census(generation, &world, &species)).await {
+        Err(error) => {
+            return Err(ApiError::Internal(error.to_string()));
+        },
+        Ok(census) => census
+    };
+
+    sw.stop();
+
+    println!("census() elapsed [{} ms]", sw.elapsed_ms());
+
+    Ok(HttpResponse::Ok().json(census))
+}
+
+#[get("/stats")]
+pub async fn stats(
+    query: web::Query<StatsQuery>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let stats: Vec<GenerationStats>;
 
-    HttpResponse::Ok()
-        .content_type(ContentType::png())
-        .body(buffer)
+    {
+        let unlocked_data = rwlock_app.read()?;
+
+        stats = unlocked_data
+            .get_stats()
+            .iter()
+            .filter(|s| query.matches(s))
+            .copied()
+            .collect();
+    }
+
+    Ok(match query.format {
+        StatsFormat::Json => HttpResponse::Ok().json(stats),
+        StatsFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv")
+            .body(stats_to_csv(stats.iter())),
+    })
+}
+
+#[get("/status")]
+pub async fn status(rwlock_app: web::Data<Arc<RwLock<Universe>>>) -> Result<HttpResponse, ApiError> {
+    let unlocked_data = rwlock_app.read()?;
+
+    let universe = &*unlocked_data;
+
+    Ok(HttpResponse::Ok().json(StatusResponse {
+        seed: universe.get_seed(),
+        generation: universe.get_generation(),
+        population: universe.get_stats().back().map(|s| s.population),
+        species: universe.get_species().len(),
+        cycle: universe.get_cycle(),
+        last_injections: universe.get_last_injections(),
+    }))
+}
+
+#[post("/seed")]
+pub async fn seed(
+    request: web::Json<SeedRequest>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let request = request.into_inner();
+    let seed = request.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+    if let Some(seeding) = &request.seeding {
+        // Server files are only read from the config or the command line
+        if let WorldSeeding::File { .. } = seeding {
+            return Err(ApiError::BadRequest(
+                "File seeding is not available through the API".to_string(),
+            ));
+        }
+        if let Err(e) = seeding.validate() {
+            return Err(ApiError::BadRequest(e));
+        }
+    }
+
+    let seeding: WorldSeeding;
+
+    {
+        let mut unlocked_data = rwlock_app.write()?;
+
+        if let Some(seeding) = &request.seeding {
+            seeding.check_species(&unlocked_data).map_err(ApiError::BadRequest)?;
+        }
+
+        let previous_seeding = unlocked_data.get_seeding().clone();
+
+        if let Some(seeding) = request.seeding {
+            unlocked_data.set_seeding(seeding);
+        }
+
+        unlocked_data.reset(seed);
+
+        if let Err(e) = seed_world(&mut unlocked_data) {
+            // Keep the seeding that worked, and the world it gives, for this and the next restart
+            unlocked_data.set_seeding(previous_seeding);
+            unlocked_data.reset(seed);
+            let _ = seed_world(&mut unlocked_data);
+            return Err(ApiError::BadRequest(e));
+        }
+
+        seeding = unlocked_data.get_seeding().clone();
+    }
+
+    println!("seed() restarted with seed {}", seed);
+
+    Ok(HttpResponse::Ok().json(SeedRequest { seed: Some(seed), seeding: Some(seeding) }))
+}
+
+#[get("/entropy/policy")]
+pub async fn get_entropy_policy(rwlock_app: web::Data<Arc<RwLock<Universe>>>) -> Result<HttpResponse, ApiError> {
+    let unlocked_data = rwlock_app.read()?;
+
+    Ok(HttpResponse::Ok().json(unlocked_data.get_entropy_policy()))
+}
+
+#[put("/entropy/policy")]
+pub async fn put_entropy_policy(
+    request: web::Json<EntropyPolicy>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let policy = request.into_inner();
+
+    if let Err(e) = policy.validate() {
+        return Err(ApiError::BadRequest(e));
+    }
+
+    {
+        let mut unlocked_data = rwlock_app.write()?;
+
+        unlocked_data.set_entropy_policy(policy.clone());
+    }
+
+    println!("put_entropy_policy() {:?}", policy);
+
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+#[get("/events")]
+pub async fn events(
+    query: web::Query<EventsQuery>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let unlocked_data = rwlock_app.read()?;
+
+    let universe = &*unlocked_data;
+
+    let events: Vec<InjectionReport> = universe
+        .get_entropy_events()
+        .iter()
+        .filter(|event| query.since.is_none_or(|since| event.id > since))
+        .filter(|event| query.from.is_none_or(|from| event.generation >= from))
+        .filter(|event| query.to.is_none_or(|to| event.generation <= to))
+        .cloned()
+        .collect();
+
+    Ok(HttpResponse::Ok().json(EventsResponse {
+        generation: universe.get_generation(),
+        events,
+    }))
+}
+
+#[get("/ws")]
+pub async fn ws(
+    request: HttpRequest,
+    body: web::Payload,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+    limits: web::Data<BoundsLimits>,
+    sender: web::Data<Sender<EngineEvent>>,
+) -> Result<HttpResponse, ApiError> {
+    let (response, session, messages) =
+        actix_ws::handle(&request, body).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let rwlock = Arc::clone(rwlock_app.get_ref());
+
+    actix_web::rt::spawn(viewport_session(
+        session,
+        messages,
+        rwlock,
+        **limits,
+        sender.subscribe(),
+    ));
+
+    Ok(response)
+}
+
+#[get("/events/stream")]
+pub async fn events_stream(sender: web::Data<Sender<EngineEvent>>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        // Keeps the compression middleware from buffering the stream
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(event_stream(sender.subscribe())))
+}
+
+#[get("/control")]
+pub async fn get_control(rwlock_app: web::Data<Arc<RwLock<Universe>>>) -> Result<HttpResponse, ApiError> {
+    let unlocked_data = rwlock_app.read()?;
+
+    Ok(HttpResponse::Ok().json(unlocked_data.get_control()))
+}
+
+#[post("/control")]
+pub async fn post_control(
+    request: web::Json<ControlRequest>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    // The engine takes the write lock every loop, a zero delay would starve readers
+    match request.delay_millis {
+        Some(delay_millis) if !(ENGINE_MIN_DELAY_MILLIS..=ENGINE_MAX_DELAY_MILLIS).contains(&delay_millis) => {
+            return Err(ApiError::BadRequest(format!(
+                "delay_millis must be {} to {}",
+                ENGINE_MIN_DELAY_MILLIS, ENGINE_MAX_DELAY_MILLIS
+            )));
+        },
+        _ => {}
+    }
+
+    let control: EngineControl;
+
+    {
+        let mut unlocked_data = rwlock_app.write()?;
+
+        let current = unlocked_data.get_control();
+
+        control = EngineControl {
+            paused: request.paused.unwrap_or(current.paused),
+            delay_millis: request.delay_millis.unwrap_or(current.delay_millis),
+        };
+
+        unlocked_data.set_control(control);
+    }
+
+    // The control event goes out when the engine loop picks the change up
+    println!("post_control() {:?}", control);
+
+    Ok(HttpResponse::Ok().json(control))
+}
+
+#[get("/region")]
+pub async fn region(
+    query: web::Query<RegionQuery>,
+    request: HttpRequest,
+    limits: web::Data<BoundsLimits>,
+    rwlock_app: web::Data<Arc<RwLock<Universe>>>,
+) -> Result<HttpResponse, ApiError> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let bounds = query.get_bounds();
+
+    bounds.validate(&limits).map_err(ApiError::BadRequest)?;
+
+    // Parsed by hand so a malformed Accept gets a JSON error like every other bad input
+    let accept = match header::Accept::parse(&request) {
+        Err(e) => {
+            return Err(ApiError::BadRequest(format!("Invalid Accept header: {}", e)));
+        },
+        Ok(accept) => accept,
+    };
+
+    let binary = match accepts_binary(&accept) {
+        None => {
+            return Err(ApiError::NotAcceptable(
+                "Region is served as application/json or application/octet-stream".to_string(),
+            ));
+        },
+        Some(binary) => binary,
+    };
+
+    let generation: usize;
+    let world: UniversePlane;
+
+    {
+        let unlocked_data = rwlock_app.read()?;
+
+        generation = unlocked_data.get_generation();
+        world = unlocked_data.get_current_world(bounds);
+    }
+
+    let mut builder = HttpResponse::Ok();
+    builder.insert_header((header::VARY, "Accept"));
+
+    let response = match (binary, query.layout) {
+        (true, _) => builder
+            .content_type(ContentType::octet_stream())
+            .body(region_binary(generation, &bounds, &world)),
+        (false, RegionLayout::Cells) => builder.json(region_cells(generation, &bounds, &world)),
+        (false, RegionLayout::Bitmap) => builder.json(region_bitmap(generation, &bounds, &world)),
+    };
+
+    sw.stop();
+
+    println!("region() elapsed [{} ms]", sw.elapsed_ms());
+
+    Ok(response)
+}
+
+// What options.mode colors, ages and activity have to be read under the same lock as the cells
+fn get_render_values(universe: &Universe, bounds: WorldBounds, mode: RenderMode) -> Result<RenderPlane, ApiError> {
+    match mode {
+        RenderMode::Cells => Ok(cell_values(&universe.get_current_world(bounds))),
+        RenderMode::Age => match universe.get_current_ages(bounds) {
+            None => Err(ApiError::BadRequest(
+                "Cell ages are not tracked, set cell_ages = true in the config".to_string(),
+            )),
+            Some(ages) => Ok(age_values(&ages)),
+        },
+        RenderMode::Heat => {
+            // Every cell is compared across every history step while the lock is held
+            let (oldest, newest) = universe.get_history_range();
+            let cell_steps = (bounds.w * bounds.h) as usize * (newest - oldest);
+            if cell_steps > RENDER_HEAT_MAX_CELL_STEPS {
+                return Err(ApiError::BadRequest(format!(
+                    "mode=heat compares {} cells over {} generations, more than {} cell steps, use a smaller region",
+                    bounds.w * bounds.h,
+                    newest - oldest,
+                    RENDER_HEAT_MAX_CELL_STEPS
+                )));
+            }
+
+            let (changes, steps) = universe.get_activity(bounds);
+            Ok(heat_values(&universe.get_current_world(bounds), &changes, steps))
+        },
+    }
+}
+
+// First acceptable type by quality, */* and a missing header mean JSON
+fn accepts_binary(accept: &header::Accept) -> Option<bool> {
+    if accept.is_empty() {
+        return Some(false);
+    }
+
+    accept.ranked().into_iter().find_map(|mime| {
+        if mime.type_() == mime::STAR {
+            Some(false)
+        } else if mime.type_() != mime::APPLICATION {
+            None
+        } else if mime.subtype() == mime::OCTET_STREAM {
+            Some(true)
+        } else if mime.subtype() == mime::JSON || mime.subtype() == mime::STAR {
+            Some(false)
+        } else {
+            None
+        }
+    })
 }
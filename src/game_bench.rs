@@ -0,0 +1,165 @@
+use image::{ImageBuffer, Rgb};
+use imageproc::{drawing, rect::Rect};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::fmt::Write;
+use stopwatch::Stopwatch;
+
+use crate::game_cli::BenchArgs;
+use crate::game_constants::{UniversePlane, CELL_DEATH, CELL_LIVE, CELL_SIZE};
+use crate::game_render::{
+    cell_values, render_image, render_svg, ImageOptions, ImageQuery, RenderCompression, RenderFormat,
+    RenderOptions, RenderQuery,
+};
+
+struct BenchResult {
+    bytes: usize,
+    elapsed_ms: f64,
+}
+
+pub fn bench_main(args: &BenchArgs) -> std::io::Result<()> {
+    println!(
+        "bench_render() {}x{} world, seed {}, {} iterations",
+        args.size, args.size, args.seed, args.iterations
+    );
+    println!(
+        "{:>8} {:>9} {:>16} {:>10}",
+        "density", "renderer", "bytes", "ms"
+    );
+
+    let query = RenderQuery::default();
+    let options = match query.get_options(args.size as isize, args.size as isize) {
+        Err(e) => {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+        },
+        Ok(options) => options
+    };
+
+    let indexed = match ImageQuery::default().get_options() {
+        Err(e) => {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+        },
+        Ok(image) => image
+    };
+    let rgb = ImageOptions { palette: false, ..indexed };
+    let fast = ImageOptions { compression: RenderCompression::Fast, ..indexed };
+    let best = ImageOptions { compression: RenderCompression::Best, ..indexed };
+    let jpeg = ImageOptions { format: RenderFormat::Jpeg, ..indexed };
+
+    for density in &args.densities {
+        let world = random_world(args.size, *density, args.seed);
+        let values = cell_values(&world);
+
+        let results = [
+            ("svg_rects", bench(args.iterations, || Ok(rects_svg(&world).into_bytes()))),
+            ("svg", bench(args.iterations, || Ok(render_svg(&values, &options).into_bytes()))),
+            ("png_rects", bench(args.iterations, || rects_png(&world, &options))),
+            ("png", bench(args.iterations, || render_image(&values, &options, &indexed))),
+            ("png_fast", bench(args.iterations, || render_image(&values, &options, &fast))),
+            ("png_best", bench(args.iterations, || render_image(&values, &options, &best))),
+            ("png_rgb", bench(args.iterations, || render_image(&values, &options, &rgb))),
+            ("jpeg", bench(args.iterations, || render_image(&values, &options, &jpeg))),
+        ];
+
+        for (renderer, result) in results {
+            match result {
+                Err(e) => eprintln!("{} {}: {}", density, renderer, e),
+                Ok(result) => println!(
+                    "{:>8} {:>9} {:>16} {:>10.1}",
+                    density, renderer, result.bytes, result.elapsed_ms
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn bench<F>(iterations: usize, render: F) -> Result<BenchResult, String>
+where
+    F: Fn() -> Result<Vec<u8>, String>,
+{
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let mut bytes = 0;
+    for _ in 0..iterations.max(1) {
+        bytes = render()?.len();
+    }
+
+    sw.stop();
+
+    Ok(BenchResult {
+        bytes,
+        elapsed_ms: sw.elapsed_ms() as f64 / iterations.max(1) as f64,
+    })
+}
+
+fn random_world(size: usize, density: f64, seed: u64) -> UniversePlane {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut world: UniversePlane = vec![vec![CELL_DEATH; size]; size];
+    for column in world.iter_mut() {
+        for cell in column.iter_mut() {
+            if rng.gen_bool(density) {
+                *cell = CELL_LIVE;
+            }
+        }
+    }
+
+    world
+}
+
+// The one <rect> per live cell SVG the runs replaced, kept as the baseline
+fn rects_svg(world: &UniversePlane) -> String {
+    let mut svgcontent = String::new();
+
+    for (a, column) in world.iter().enumerate() {
+        for (b, cell) in column.iter().enumerate() {
+            if *cell == CELL_LIVE {
+                let _ = write!(
+                    svgcontent,
+                    "  <rect x='{}' y='{}' width='{}' height='{}' fill='black' />",
+                    a * CELL_SIZE,
+                    b * CELL_SIZE,
+                    CELL_SIZE,
+                    CELL_SIZE
+                );
+            }
+        }
+    }
+
+    svgcontent
+}
+
+// The RGB PNG with one draw call per live cell the direct pixel rows replaced, kept as the baseline
+fn rects_png(world: &UniversePlane, options: &RenderOptions) -> Result<Vec<u8>, String> {
+    let (width, height) = options.get_size(world.len(), world[0].len());
+
+    let mut img = ImageBuffer::new(width as u32, height as u32);
+
+    drawing::draw_filled_rect_mut(
+        &mut img,
+        Rect::at(0, 0).of_size(width as u32, height as u32),
+        Rgb(options.bg.0),
+    );
+
+    for (a, column) in world.iter().enumerate() {
+        for (b, cell) in column.iter().enumerate() {
+            if *cell == CELL_LIVE {
+                drawing::draw_filled_rect_mut(
+                    &mut img,
+                    Rect::at((a * options.scale) as i32, (b * options.scale) as i32)
+                        .of_size(options.scale as u32, options.scale as u32),
+                    Rgb(options.fg.0),
+                );
+            }
+        }
+    }
+
+    let mut buffer: Vec<u8> = Vec::new();
+    if let Err(e) = img.write_to(&mut std::io::Cursor::new(&mut buffer), image::ImageOutputFormat::Png) {
+        return Err(e.to_string());
+    }
+
+    Ok(buffer)
+}
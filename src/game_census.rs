@@ -0,0 +1,154 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+use crate::game_classifier::{classify_cells_limited, CellSet, PatternClass};
+use crate::game_constants::{
+    UniversePlane, CELL_LIVE, CENSUS_MAX_GENERATIONS, CENSUS_MAX_ISLAND_POPULATION,
+};
+use crate::game_species::Species;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CensusEntry {
+    pub code: String,
+    pub name: Option<String>,
+    pub class: PatternClass,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Census {
+    pub generation: usize,
+    pub population: usize,
+    pub islands: usize,
+    pub objects: Vec<CensusEntry>,
+}
+
+// Group live cells closer than two cells apart, unwrapping the torus edges
+pub fn find_islands(world: &UniversePlane) -> Vec<CellSet> {
+    let xsize = world.len() as isize;
+    let ysize = match world.first() {
+        None => return Vec::new(),
+        Some(column) => column.len() as isize,
+    };
+
+    let mut visited = vec![vec![false; ysize as usize]; xsize as usize];
+    let mut islands: Vec<CellSet> = Vec::new();
+
+    for a in 0..xsize {
+        for b in 0..ysize {
+            if visited[a as usize][b as usize] || world[a as usize][b as usize] != CELL_LIVE {
+                continue;
+            }
+
+            let mut island = CellSet::new();
+            let mut queue: VecDeque<(isize, isize)> = VecDeque::new();
+
+            visited[a as usize][b as usize] = true;
+            queue.push_back((a, b));
+
+            while let Some((x, y)) = queue.pop_front() {
+                island.insert((x, y));
+                for i in -2..=2 {
+                    for j in -2..=2 {
+                        let (xw, yw) = ((x + i).rem_euclid(xsize), (y + j).rem_euclid(ysize));
+                        if !visited[xw as usize][yw as usize]
+                            && world[xw as usize][yw as usize] == CELL_LIVE
+                        {
+                            visited[xw as usize][yw as usize] = true;
+                            queue.push_back((x + i, y + j));
+                        }
+                    }
+                }
+            }
+
+            islands.push(island);
+        }
+    }
+
+    islands
+}
+
+// Group live cells closer than two cells apart on an unbounded plane
+pub fn find_cell_islands(cells: &CellSet) -> Vec<CellSet> {
+    let mut remaining = cells.clone();
+    let mut islands: Vec<CellSet> = Vec::new();
+
+    while let Some(&start) = remaining.iter().next() {
+        let mut island = CellSet::new();
+        let mut queue: VecDeque<(isize, isize)> = VecDeque::new();
+
+        remaining.remove(&start);
+        queue.push_back(start);
+
+        while let Some((x, y)) = queue.pop_front() {
+            island.insert((x, y));
+            for i in -2..=2 {
+                for j in -2..=2 {
+                    if remaining.remove(&(x + i, y + j)) {
+                        queue.push_back((x + i, y + j));
+                    }
+                }
+            }
+        }
+
+        islands.push(island);
+    }
+
+    islands
+}
+
+// Classify each island and count them by canonical code
+pub fn tally_islands(islands: &[CellSet], species: &[Species]) -> Vec<CensusEntry> {
+    let known: HashMap<&str, &str> = species
+        .iter()
+        .filter_map(|s| {
+            s.metadata
+                .classification
+                .as_ref()
+                .filter(|report| report.class != PatternClass::Other)
+                .map(|report| (report.code.as_str(), s.name.as_str()))
+        })
+        .collect();
+
+    let mut counts: HashMap<String, CensusEntry> = HashMap::new();
+
+    for island in islands {
+        let (code, class) = match island.len() > CENSUS_MAX_ISLAND_POPULATION {
+            true => ("other".to_string(), PatternClass::Other),
+            false => {
+                let report = classify_cells_limited(island, CENSUS_MAX_GENERATIONS);
+                (report.code, report.class)
+            }
+        };
+
+        counts
+            .entry(code.clone())
+            .or_insert_with(|| CensusEntry {
+                name: known.get(code.as_str()).map(|name| name.to_string()),
+                code,
+                class,
+                count: 0,
+            })
+            .count += 1;
+    }
+
+    let mut objects: Vec<CensusEntry> = counts.into_values().collect();
+    objects.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(&b.code)));
+
+    objects
+}
+
+pub fn take_census(generation: usize, world: &UniversePlane, species: &[Species]) -> Census {
+    let islands = find_islands(world);
+
+    let population = islands.iter().map(|island| island.len()).sum();
+
+    let objects = tally_islands(&islands, species);
+
+    Census {
+        generation,
+        population,
+        islands: islands.len(),
+        objects,
+    }
+}
@@ -0,0 +1,308 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::game_constants::{
+    UniverseCell, UniversePlane, CELL_DEATH, CELL_LIVE, CLASSIFY_MAX_GENERATIONS,
+    CLASSIFY_MAX_POPULATION,
+};
+use crate::game_engine::next_cell_state;
+
+pub type CellSet = HashSet<(isize, isize)>;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PatternClass {
+    StillLife,
+    Oscillator { period: usize },
+    Spaceship { period: usize, dx: isize, dy: isize },
+    Other,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PatternReport {
+    pub class: PatternClass,
+    // Canonical apgcode style name, equal for every phase and orientation
+    pub code: String,
+    pub generations: usize,
+    pub population_min: usize,
+    pub population_max: usize,
+    pub population_mean: f64,
+}
+
+impl PatternClass {
+    #[inline]
+    pub fn get_period(&self) -> Option<usize> {
+        match self {
+            PatternClass::StillLife => Some(1),
+            PatternClass::Oscillator { period } => Some(*period),
+            PatternClass::Spaceship { period, .. } => Some(*period),
+            PatternClass::Other => None,
+        }
+    }
+
+    pub fn get_velocity(&self) -> Option<String> {
+        match *self {
+            PatternClass::Spaceship { period, dx, dy } => {
+                let (ax, ay) = (dx.unsigned_abs(), dy.unsigned_abs());
+                let (direction, speed) = if ax == 0 || ay == 0 {
+                    ("orthogonal", ax.max(ay))
+                } else if ax == ay {
+                    ("diagonal", ax)
+                } else {
+                    return Some(format!("({},{})c/{} oblique", ax.max(ay), ax.min(ay), period));
+                };
+                let divisor = gcd(speed, period);
+                let (speed, period) = (speed / divisor, period / divisor);
+                match speed {
+                    1 => Some(format!("c/{} {}", period, direction)),
+                    _ => Some(format!("{}c/{} {}", speed, period, direction)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_tag(&self) -> &'static str {
+        match self {
+            PatternClass::StillLife => "still_life",
+            PatternClass::Oscillator { .. } => "oscillator",
+            PatternClass::Spaceship { .. } => "spaceship",
+            PatternClass::Other => "other",
+        }
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+pub fn cells_from_plane(entity: &UniversePlane) -> CellSet {
+    let mut cells = CellSet::new();
+
+    for (a, column) in entity.iter().enumerate() {
+        for (b, cell) in column.iter().enumerate() {
+            if *cell == CELL_LIVE {
+                cells.insert((a as isize, b as isize));
+            }
+        }
+    }
+
+    cells
+}
+
+pub fn step_cells(cells: &CellSet) -> CellSet {
+    let mut neighbors: HashMap<(isize, isize), UniverseCell> = HashMap::new();
+
+    for (x, y) in cells {
+        for i in -1..=1 {
+            for j in -1..=1 {
+                if i != 0 || j != 0 {
+                    *neighbors.entry((x + i, y + j)).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    neighbors
+        .into_iter()
+        .filter(|(cell, neighbors_count)| {
+            let old_state = match cells.contains(cell) {
+                true => CELL_LIVE,
+                false => CELL_DEATH,
+            };
+            next_cell_state(old_state, *neighbors_count) == CELL_LIVE
+        })
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+// Sorted cells relative to the bounding box corner, plus that corner
+pub fn normalize_cells(cells: &CellSet) -> (Vec<(isize, isize)>, (isize, isize)) {
+    let min_x = cells.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+
+    let mut normalized: Vec<(isize, isize)> =
+        cells.iter().map(|(x, y)| (x - min_x, y - min_y)).collect();
+    normalized.sort_unstable();
+
+    (normalized, (min_x, min_y))
+}
+
+pub fn classify_cells(cells: &CellSet) -> PatternReport {
+    classify_cells_limited(cells, CLASSIFY_MAX_GENERATIONS)
+}
+
+pub fn classify_cells_limited(cells: &CellSet, max_generations: usize) -> PatternReport {
+    let (shape_origin, offset_origin) = normalize_cells(cells);
+
+    let mut phases: Vec<CellSet> = vec![cells.clone()];
+    let mut class = PatternClass::Other;
+
+    for generation in 1..=max_generations {
+        let current = step_cells(&phases[generation - 1]);
+
+        if current.len() == cells.len() {
+            let (shape, offset) = normalize_cells(&current);
+            if shape == shape_origin {
+                let (dx, dy) = (offset.0 - offset_origin.0, offset.1 - offset_origin.1);
+                class = match (generation, dx, dy) {
+                    (1, 0, 0) => PatternClass::StillLife,
+                    (period, 0, 0) => PatternClass::Oscillator { period },
+                    (period, dx, dy) => PatternClass::Spaceship { period, dx, dy },
+                };
+                // Phases and statistics cover exactly one period
+                break;
+            }
+        }
+
+        let stop = current.is_empty() || current.len() > CLASSIFY_MAX_POPULATION;
+        phases.push(current);
+        if stop {
+            break;
+        }
+    }
+
+    let populations: Vec<usize> = phases.iter().map(|phase| phase.len()).collect();
+    let generations = populations.len();
+
+    PatternReport {
+        class,
+        code: canonical_code(&class, &phases),
+        generations,
+        population_min: populations.iter().copied().min().unwrap_or(0),
+        population_max: populations.iter().copied().max().unwrap_or(0),
+        population_mean: populations.iter().sum::<usize>() as f64 / generations as f64,
+    }
+}
+
+fn canonical_code(class: &PatternClass, phases: &[CellSet]) -> String {
+    let prefix = match class {
+        PatternClass::StillLife => format!("xs{}", phases[0].len()),
+        PatternClass::Oscillator { period } => format!("xp{}", period),
+        PatternClass::Spaceship { period, .. } => format!("xq{}", period),
+        PatternClass::Other => return "other".to_string(),
+    };
+
+    let mut best: Option<String> = None;
+
+    for phase in phases {
+        for transform in 0..8 {
+            let transformed: CellSet = phase
+                .iter()
+                .map(|&(x, y)| match transform {
+                    0 => (x, y),
+                    1 => (-y, x),
+                    2 => (-x, -y),
+                    3 => (y, -x),
+                    4 => (-x, y),
+                    5 => (x, -y),
+                    6 => (y, x),
+                    _ => (-y, -x),
+                })
+                .collect();
+            let (normalized, _) = normalize_cells(&transformed);
+            let code = wechsler_code(&normalized);
+            best = match best {
+                Some(b) if (b.len(), &b) <= (code.len(), &code) => Some(b),
+                _ => Some(code),
+            };
+        }
+    }
+
+    format!("{}_{}", prefix, best.unwrap_or_default())
+}
+
+fn push_zero_run(encoded: &mut String, zeros: usize) {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut zeros = zeros;
+
+    while zeros > 0 {
+        let run = zeros.min(39);
+        match run {
+            1 => encoded.push('0'),
+            2 => encoded.push('w'),
+            3 => encoded.push('x'),
+            _ => {
+                encoded.push('y');
+                encoded.push(DIGITS[run - 4] as char);
+            }
+        }
+        zeros -= run;
+    }
+}
+
+// Extended Wechsler format: strips of five rows, one base 32 digit per column
+fn wechsler_code(cells: &[(isize, isize)]) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let width = cells.iter().map(|(x, _)| x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|(_, y)| y + 1).max().unwrap_or(0);
+    let set: HashSet<&(isize, isize)> = cells.iter().collect();
+
+    let mut strips: Vec<String> = Vec::new();
+
+    for strip in 0..(height + 4) / 5 {
+        let mut columns: Vec<usize> = (0..width)
+            .map(|x| {
+                (0..5)
+                    .filter(|bit| set.contains(&(x, strip * 5 + bit)))
+                    .fold(0, |acc, bit| acc | (1 << bit))
+            })
+            .collect();
+        while columns.last() == Some(&0) {
+            columns.pop();
+        }
+
+        let mut encoded = String::new();
+        let mut zeros = 0;
+        for column in columns {
+            if column == 0 {
+                zeros += 1;
+            } else {
+                push_zero_run(&mut encoded, zeros);
+                zeros = 0;
+                encoded.push(DIGITS[column] as char);
+            }
+        }
+        strips.push(encoded);
+    }
+
+    strips.join("z")
+}
+
+pub fn classify_pattern(entity: &UniversePlane) -> PatternReport {
+    classify_cells(&cells_from_plane(entity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_species::species_rle_to_vec;
+
+    #[test]
+    fn blinker_is_a_period_two_oscillator() {
+        let blinker: UniversePlane = vec![vec![CELL_LIVE]; 3];
+
+        let report = classify_pattern(&blinker);
+
+        assert_eq!(report.class, PatternClass::Oscillator { period: 2 });
+        assert_eq!(report.population_min, 3);
+        assert_eq!(report.population_max, 3);
+    }
+
+    #[test]
+    fn glider_is_a_c4_diagonal_spaceship() {
+        let glider = species_rle_to_vec("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+
+        let report = classify_pattern(&glider);
+
+        assert_eq!(report.class.get_tag(), "spaceship");
+        assert_eq!(report.class.get_period(), Some(4));
+        assert_eq!(report.class.get_velocity().as_deref(), Some("c/4 diagonal"));
+    }
+}
@@ -0,0 +1,130 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::game_animation::AnimationFormat;
+use crate::game_constants::{ANIMATION_DEFAULT_FPS, ANIMATION_MARGIN, CELL_SIZE, SEEDING_TILE_GAP};
+use crate::game_render::RenderTheme;
+use crate::game_seeding::{SeedPattern, WorldSeeding};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Game of Life Rust Implementation")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub serve: ServeArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the simulation and the web server (default)
+    Serve(ServeArgs),
+    /// Run random soups to stabilization and catalogue the objects found
+    Soup(SoupArgs),
+    /// Measure output size and render time of the image endpoints on random worlds
+    Bench(BenchArgs),
+    /// Run a pattern file for some generations and write them as an animated GIF or APNG
+    Animate(AnimateArgs),
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct ServeArgs {
+    /// Seed of the simulation, overrides the config file
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Start from a random fill with this density, drawn from the seed
+    #[arg(long, value_name = "DENSITY", group = "seeding")]
+    pub random: Option<f64>,
+    /// Start with a library pattern at a position, repeatable
+    #[arg(long, value_name = "NAME@X,Y", group = "seeding")]
+    pub pattern: Vec<SeedPattern>,
+    /// Start with a library pattern tiled over the whole world
+    #[arg(long, value_name = "NAME", group = "seeding")]
+    pub tile: Option<String>,
+    /// Dead cells between tiles
+    #[arg(long, requires = "tile")]
+    pub tile_gap: Option<isize>,
+    /// Start from a .cells, .rle or .lif pattern or a /gettext snapshot saved as .txt
+    #[arg(long, value_name = "PATH", group = "seeding")]
+    pub file: Option<PathBuf>,
+}
+
+impl ServeArgs {
+    // The seeding picked on the command line, it overrides the config file
+    pub fn seeding(&self) -> Option<WorldSeeding> {
+        if let Some(density) = self.random {
+            return Some(WorldSeeding::Random { density });
+        }
+        if !self.pattern.is_empty() {
+            return Some(WorldSeeding::Patterns { patterns: self.pattern.clone() });
+        }
+        if let Some(name) = &self.tile {
+            return Some(WorldSeeding::Tile {
+                name: name.clone(),
+                gap: self.tile_gap.unwrap_or(SEEDING_TILE_GAP),
+                area: None,
+            });
+        }
+        self.file.as_ref().map(|path| WorldSeeding::File { path: path.clone(), x: 0, y: 0 })
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SoupArgs {
+    /// Number of soups to run
+    #[arg(long, default_value_t = 1000)]
+    pub soups: u64,
+    /// Seed of the first soup, soup n uses seed + n
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Worker threads
+    #[arg(long, default_value_t = 4)]
+    pub threads: usize,
+    /// JSON report file
+    #[arg(long, default_value = "soup_report.json")]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BenchArgs {
+    /// Side of the square world, in cells
+    #[arg(long, default_value_t = 1024)]
+    pub size: usize,
+    /// Fractions of live cells, one world each
+    #[arg(long, value_delimiter = ',', default_values_t = [0.05, 0.3, 0.5])]
+    pub densities: Vec<f64>,
+    /// Seed of the random worlds
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Renders per measurement, the time is the mean
+    #[arg(long, default_value_t = 3)]
+    pub iterations: usize,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AnimateArgs {
+    /// Pattern file, .cells, .rle, .lif or a /gettext .txt snapshot
+    pub pattern: PathBuf,
+    /// Generations after the pattern itself, one frame each
+    #[arg(long, default_value_t = 100)]
+    pub generations: usize,
+    /// .gif, or .png / .apng for an APNG
+    #[arg(long, default_value = "animation.gif")]
+    pub output: PathBuf,
+    /// Overrides the format picked from the output extension
+    #[arg(long, value_enum)]
+    pub format: Option<AnimationFormat>,
+    #[arg(long, default_value_t = ANIMATION_DEFAULT_FPS)]
+    pub fps: u16,
+    /// Pixels per cell
+    #[arg(long, default_value_t = CELL_SIZE)]
+    pub scale: usize,
+    #[arg(long, value_enum)]
+    pub theme: Option<RenderTheme>,
+    #[arg(long)]
+    pub grid: bool,
+    /// Empty cells around the area every generation fits in
+    #[arg(long, default_value_t = ANIMATION_MARGIN)]
+    pub margin: isize,
+}
@@ -0,0 +1,89 @@
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use crate::game_constants::{CONFIG_ENV_VAR, CONFIG_FILE, CYCLE_TRIGGER_GENERATIONS};
+use crate::game_entropy::EntropyPolicy;
+use crate::game_period::StableAction;
+use crate::game_seeding::WorldSeeding;
+use crate::game_universe::BoundsLimits;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameConfig {
+    // Files or directories holding .cells, .lif and .rle patterns
+    pub species_paths: Vec<PathBuf>,
+    // Reload the species library when a pattern file changes
+    pub species_watch: bool,
+    // Seed of the simulation random generator, random when missing
+    pub seed: Option<u64>,
+    // What the world holds at generation zero, also after POST /seed
+    pub seeding: WorldSeeding,
+    // Initial entropy policy, it can be replaced at runtime
+    pub entropy: EntropyPolicy,
+    // What to do once the whole world has been cycling for stable_after generations
+    pub stable_action: StableAction,
+    pub stable_after: usize,
+    // Largest region the API serves in one request
+    pub limits: BoundsLimits,
+    // Count how long every cell has been alive, for mode=age renders
+    pub cell_ages: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            species_paths: vec![PathBuf::from("species")],
+            species_watch: true,
+            seed: None,
+            seeding: WorldSeeding::Empty,
+            entropy: EntropyPolicy::default(),
+            stable_action: StableAction::None,
+            stable_after: CYCLE_TRIGGER_GENERATIONS,
+            limits: BoundsLimits::default(),
+            cell_ages: true,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load() -> GameConfig {
+        let (path, required) = match std::env::var(CONFIG_ENV_VAR) {
+            Err(_) => (PathBuf::from(CONFIG_FILE), false),
+            Ok(path) => (PathBuf::from(path), true),
+        };
+
+        GameConfig::load_from(&path, required)
+    }
+
+    pub fn load_from(path: &PathBuf, required: bool) -> GameConfig {
+        match read_to_string(path) {
+            Err(e) => {
+                if required {
+                    eprintln!("Config {}: {:?}", path.display(), e);
+                }
+                GameConfig::default()
+            },
+            Ok(content) => match toml::from_str::<GameConfig>(content.as_str()) {
+                Err(e) => {
+                    eprintln!("Config {}: {}", path.display(), e);
+                    GameConfig::default()
+                },
+                Ok(config) => match config
+                    .entropy
+                    .validate()
+                    .and(config.seeding.validate())
+                    .and(config.limits.validate()) {
+                    Err(e) => {
+                        eprintln!("Config {}: {}", path.display(), e);
+                        GameConfig::default()
+                    },
+                    Ok(_) => {
+                        println!("Loaded config: {}", path.display());
+                        config
+                    }
+                }
+            }
+        }
+    }
+}
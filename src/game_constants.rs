@@ -1,6 +1,5 @@
 pub type UniverseCell = u8;
 pub type UniversePlane = Vec<Vec<UniverseCell>>;
-pub type UniversePlaneSet = Vec<UniversePlane>;
 
 pub const CELL_DEATH: UniverseCell = 0;
 pub const CELL_LIVE: UniverseCell = 1;
@@ -14,10 +13,69 @@ pub const HISTORY_SIZE: isize = 100;
 pub const WORLD_SIZE_X: isize = MAX_X - MIN_X + 1;
 pub const WORLD_SIZE_Y: isize = MAX_Y - MIN_Y + 1;
 
+pub const DEFAULT_RULE: &str = "B3/S23";
+
 pub const CELL_SIZE: usize = 4;
 
 pub const ENGINE_LOOP_DELAY_MILLIS: u64 = 100;
-pub const ENTROPY_LOOP_DELAY_MILLIS: u64 = 10000;
+pub const ENGINE_MIN_DELAY_MILLIS: u64 = 10;
+pub const ENGINE_MAX_DELAY_MILLIS: u64 = 10000;
+pub const ENTROPY_INTERVAL_GENERATIONS: usize = 100;
 
 pub const API_ADDRESS: &str = "127.0.0.1";
 pub const API_PORT: u16 = 8080;
+
+pub const CONFIG_FILE: &str = "gol_rust.toml";
+pub const CONFIG_ENV_VAR: &str = "GOL_RUST_CONFIG";
+
+pub const LIBRARY_RELOAD_DELAY_MILLIS: u64 = 500;
+
+pub const CLASSIFY_MAX_GENERATIONS: usize = 1024;
+pub const CLASSIFY_MAX_POPULATION: usize = 4096;
+
+pub const CENSUS_MAX_GENERATIONS: usize = 64;
+pub const CENSUS_MAX_ISLAND_POPULATION: usize = 512;
+
+pub const SOUP_SIZE: usize = 16;
+pub const SOUP_DENSITY: f64 = 0.5;
+pub const SOUP_MAX_GENERATIONS: usize = 8192;
+pub const SOUP_MAX_POPULATION: usize = 20000;
+pub const SOUP_STABLE_WINDOW: usize = 120;
+pub const SOUP_MAX_PERIOD: usize = 6;
+pub const SOUP_RARE_MIN_PERIOD: usize = 3;
+
+pub const STATS_HISTORY_SIZE: usize = 10000;
+
+pub const CYCLE_HISTORY_SIZE: usize = 1000;
+pub const CYCLE_TRIGGER_GENERATIONS: usize = 200;
+
+pub const ENTROPY_EMPTY_ATTEMPTS: usize = 16;
+pub const ENTROPY_MAX_SOUP_SIZE: usize = 256;
+pub const ENTROPY_MAX_METEORS: usize = 64;
+pub const ENTROPY_EVENTS_SIZE: usize = 1000;
+pub const ENTROPY_MAX_INTERVAL: usize = 1_000_000;
+
+pub const SEEDING_TILE_GAP: isize = 2;
+
+pub const CELLS_MAX_EDITS: usize = (WORLD_SIZE_X * WORLD_SIZE_Y) as usize;
+
+pub const STREAM_CHANNEL_SIZE: usize = 16;
+pub const STREAM_FRAME_KEYFRAME: u8 = 1;
+pub const STREAM_FRAME_DELTA: u8 = 2;
+
+pub const RENDER_MAX_SCALE: usize = 16;
+pub const RENDER_MAX_ZOOM_OUT: usize = 64;
+pub const RENDER_MAX_PIXELS: usize = 4096 * 4096;
+pub const RENDER_MAX_SHADES: usize = 254;
+pub const RENDER_DEFAULT_QUALITY: u8 = 85;
+pub const RENDER_AGE_SATURATION: u32 = 1000;
+pub const RENDER_HEAT_MAX_CELL_STEPS: usize = 32 * 1024 * 1024;
+
+pub const ANIMATION_DEFAULT_FPS: u16 = 10;
+pub const ANIMATION_MAX_FPS: u16 = 50;
+pub const ANIMATION_MAX_PIXELS: usize = 256 * 1024 * 1024;
+pub const ANIMATION_MAX_CELLS: usize = 16 * 1024 * 1024;
+pub const ANIMATION_MARGIN: isize = 4;
+
+pub const TILE_SIZE: usize = 256;
+pub const TILE_MAX_ZOOM: u32 = 6;
@@ -1,17 +1,44 @@
-use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
-    sync::{mpsc::Receiver, Arc, RwLock},
+    sync::{Arc, RwLock},
     thread, time,
 };
 use stopwatch::Stopwatch;
+use tokio::sync::broadcast::Sender;
 
 use crate::game_constants::{
-    UniversePlane, CELL_DEATH, CELL_LIVE, ENGINE_LOOP_DELAY_MILLIS, HISTORY_SIZE, MAX_X, MAX_Y,
+    UniverseCell, CELL_DEATH, CELL_LIVE, ENGINE_LOOP_DELAY_MILLIS, HISTORY_SIZE, MAX_X, MAX_Y,
     MIN_X, MIN_Y,
 };
+use crate::game_config::GameConfig;
+use crate::game_entropy::{entropy_step, inject_entropy};
+use crate::game_period::{zobrist_key, StableAction};
+use crate::game_stats::GenerationStats;
+use crate::game_stream::EngineEvent;
 use crate::game_universe::Universe;
 
-pub fn engine_loop(rwlock: Arc<RwLock<Universe>>, receiver: &Receiver<UniversePlane>) -> () {
+// Runtime controls of the engine loop, changed through POST /control
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineControl {
+    pub paused: bool,
+    // Pause between two generations
+    pub delay_millis: u64,
+}
+
+impl Default for EngineControl {
+    fn default() -> EngineControl {
+        EngineControl {
+            paused: false,
+            delay_millis: ENGINE_LOOP_DELAY_MILLIS,
+        }
+    }
+}
+
+pub fn engine_loop(
+    rwlock: Arc<RwLock<Universe>>,
+    config: &GameConfig,
+    sender: Sender<EngineEvent>,
+) {
     // Init
     let dimensions = Universe::get_dimensions();
 
@@ -20,10 +47,13 @@ pub fn engine_loop(rwlock: Arc<RwLock<Universe>>, receiver: &Receiver<UniversePl
         dimensions.0, dimensions.1, dimensions.2
     );
 
-    let ten_millis = time::Duration::from_millis(ENGINE_LOOP_DELAY_MILLIS);
+    // Controls last announced, changes are sent once the loop applies them
+    let mut applied = EngineControl::default();
 
     loop {
         let current_time: isize;
+        let control: EngineControl;
+        let mut events: Vec<EngineEvent> = Vec::new();
 
         let mut sw: Stopwatch = Stopwatch::start_new();
 
@@ -38,25 +68,64 @@ pub fn engine_loop(rwlock: Arc<RwLock<Universe>>, receiver: &Receiver<UniversePl
             let universe = &mut *unlocked_data;
 
             current_time = universe.get_current_time();
-
-            generate_next_time(universe, receiver);
+            control = universe.get_control();
+
+            if control != applied {
+                events.push(EngineEvent::Control(control));
+                applied = control;
+            }
+
+            if !control.paused {
+                let last_event = last_event_id(universe);
+
+                generate_next_time(universe, config);
+
+                sw.stop();
+
+                events.push(EngineEvent::Generation {
+                    generation: universe.get_generation(),
+                    population: universe.get_stats().back().map_or(0, |s| s.population),
+                    elapsed_ms: sw.elapsed_ms(),
+                });
+
+                events.extend(
+                    universe
+                        .get_entropy_events()
+                        .iter()
+                        .filter(|event| event.id > last_event)
+                        .map(|event| EngineEvent::Injection(event.clone())),
+                );
+            }
         }
 
-        sw.stop();
+        if !control.paused {
+            println!(
+                "scheduler_loop() elapsed {} [{} ms]",
+                current_time,
+                sw.elapsed_ms()
+            );
+        }
 
-        println!(
-            "scheduler_loop() elapsed {} [{} ms]",
-            current_time,
-            sw.elapsed_ms()
-        );
+        // Nobody listening is not an error
+        for event in events {
+            let _ = sender.send(event);
+        }
 
-        thread::sleep(ten_millis);
+        thread::sleep(time::Duration::from_millis(control.delay_millis));
     }
 }
 
-fn generate_next_time(universe: &mut Universe, receiver: &Receiver<UniversePlane>) -> () {
+fn last_event_id(universe: &Universe) -> usize {
+    universe.get_entropy_events().back().map_or(0, |event| event.id)
+}
+
+fn generate_next_time(universe: &mut Universe, config: &GameConfig) {
     let next_time: isize = (universe.get_current_time() + 1) % HISTORY_SIZE;
 
+    // Births, deaths and activity count the rule step only, the population is recounted after placements and entropy
+    let mut stats = GenerationStats::new(universe.get_generation() + 1);
+    let mut hash: u64 = 0;
+
     for x_pos in MIN_X..=MAX_X {
         for y_pos in MIN_Y..=MAX_Y {
             let (x_index, y_index) = universe.position_to_index(x_pos, y_pos);
@@ -72,74 +141,94 @@ fn generate_next_time(universe: &mut Universe, receiver: &Receiver<UniversePlane
                 + universe.get_current_time_cell(x_index + 1, y_index)
                 + universe.get_current_time_cell(x_index + 1, y_index + 1);
 
-            universe.set_cell_low_level(
-                next_time,
-                x_index,
-                y_index,
-                match old_state {
-                    CELL_LIVE => match neighbors_count {
-                        2 | 3 => CELL_LIVE,
-                        _ => CELL_DEATH,
-                    },
-                    _ => match neighbors_count {
-                        3 => CELL_LIVE,
-                        _ => CELL_DEATH,
-                    },
-                },
-            );
+            let new_state = next_cell_state(old_state, neighbors_count);
+
+            universe.set_cell_low_level(next_time, x_index, y_index, new_state);
+
+            stats.count_cell(x_pos, y_pos, new_state == CELL_LIVE, new_state != old_state);
+
+            if new_state == CELL_LIVE {
+                hash ^= zobrist_key(Universe::map_3d_to_1d_index(0, x_index, y_index));
+            }
         }
     }
 
-    match receiver.try_recv() {
-        Err(_) => {},
-        Ok(entity) => {
-            inject_entropy(universe, next_time, entity);
-        }
+    universe.push_stats(stats);
+
+    let placements = universe.take_placements();
+    let mut modified = !placements.is_empty();
+
+    for placement in placements {
+        universe.place_entity(
+            next_time,
+            placement.x_pos,
+            placement.y_pos,
+            &placement.entity,
+            placement.mode,
+        );
     }
 
     universe.set_current_time(next_time);
-}
+    universe.next_generation();
 
-fn inject_entropy(universe: &mut Universe, next_time: isize, entity: UniversePlane) -> () {
-    let (x_pos, y_pos) = (
-        rand::thread_rng().gen_range(MIN_X..=MAX_X),
-        rand::thread_rng().gen_range(MIN_Y..=MAX_Y),
-    );
+    if modified {
+        universe.recount_population();
+    }
 
-    let entity_xsize = entity.len() as isize;
-    let entity_ysize = entity[0].len() as isize;
+    // The injection rate reads the population with this generation's placements in
+    if entropy_step(universe, next_time) {
+        universe.recount_population();
+        modified = true;
+    }
 
-    universe.set_cell(next_time, x_pos, y_pos, -1, -1, CELL_DEATH);
-    for i in 0..entity_xsize {
-        universe.set_cell(next_time, x_pos, y_pos, i, -1, CELL_DEATH);
+    if modified {
+        hash = universe.hash_plane(next_time);
     }
-    universe.set_cell(next_time, x_pos, y_pos, entity_xsize, -1, CELL_DEATH);
-
-    for j in 0..entity_ysize {
-        universe.set_cell(next_time, x_pos, y_pos, -1, j, CELL_DEATH);
-        for i in 0..entity_xsize {
-            universe.set_cell(
-                next_time,
-                x_pos,
-                y_pos,
-                i,
-                j,
-                entity[i as usize][j as usize],
-            );
-        }
-        universe.set_cell(next_time, x_pos, y_pos, entity_xsize, j, CELL_DEATH);
+
+    universe.update_ages();
+
+    match universe.observe_hash(hash) {
+        Some(cycle) if universe.get_generation() - cycle.since >= config.stable_after => {
+            apply_stable_action(universe, config.stable_action);
+        },
+        _ => {}
     }
+}
+
+fn apply_stable_action(universe: &mut Universe, action: StableAction) {
+    let current_time = universe.get_current_time();
 
-    universe.set_cell(next_time, x_pos, y_pos, -1, entity_ysize, CELL_DEATH);
-    for i in 0..entity_xsize {
-        universe.set_cell(next_time, x_pos, y_pos, i, entity_ysize, CELL_DEATH);
+    match action {
+        StableAction::None => {
+            return;
+        },
+        StableAction::Entropy => {},
+        StableAction::Reset => {
+            universe.clear_plane(current_time);
+        }
     }
-    universe.set_cell(
-        next_time,
-        x_pos,
-        y_pos,
-        entity_xsize,
-        entity_ysize,
-        CELL_DEATH,
+
+    println!(
+        "apply_stable_action() {:?} at generation {}",
+        action,
+        universe.get_generation()
     );
+
+    inject_entropy(universe, current_time);
+
+    universe.refresh_current();
+}
+
+#[inline]
+pub fn next_cell_state(old_state: UniverseCell, neighbors_count: UniverseCell) -> UniverseCell {
+    match old_state {
+        CELL_LIVE => match neighbors_count {
+            2 | 3 => CELL_LIVE,
+            _ => CELL_DEATH,
+        },
+        _ => match neighbors_count {
+            3 => CELL_LIVE,
+            _ => CELL_DEATH,
+        },
+    }
 }
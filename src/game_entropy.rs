@@ -1,62 +1,498 @@
 use rand::prelude::*;
-use std::{
-    sync::{mpsc::Sender, Arc, RwLock},
-    thread, time,
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::game_classifier::{classify_pattern, PatternClass};
+use crate::game_constants::{
+    UniversePlane, CELL_DEATH, CELL_LIVE, ENTROPY_EMPTY_ATTEMPTS, ENTROPY_INTERVAL_GENERATIONS,
+    ENTROPY_MAX_INTERVAL, ENTROPY_MAX_METEORS, ENTROPY_MAX_SOUP_SIZE, MAX_X, MAX_Y, MIN_X, MIN_Y,
+    WORLD_SIZE_X, WORLD_SIZE_Y,
 };
-use stopwatch::Stopwatch;
+use crate::game_species::{species_nop, Species, SpeciesTransform};
+use crate::game_universe::{check_position, PlacementMode, Universe};
 
-use crate::game_constants::{ ENTROPY_LOOP_DELAY_MILLIS, UniversePlane };
-use crate::game_species::species_nop;
-use crate::game_universe::Universe;
+// Which species gets injected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EntropySelection {
+    #[default]
+    Uniform,
+    // Relative weights by species name, species not listed get default_weight
+    Weighted {
+        weights: HashMap<String, f64>,
+        #[serde(default = "default_weight")]
+        default_weight: f64,
+    },
+}
 
-pub fn entropy_loop(rwlock: Arc<RwLock<Universe>>, sender: &Sender<UniversePlane>) -> () {
-    // Init
-    let dimensions = Universe::get_dimensions();
+// How often injections happen
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EntropyRate {
+    #[default]
+    Fixed,
+    // The interval shrinks with the population below target, down to min_interval
+    Population { target: usize, min_interval: usize },
+}
 
-    println!(
-        "entropy_loop() {} {} {}",
-        dimensions.0, dimensions.1, dimensions.2
-    );
+// Where a species or soup patch lands
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EntropyPlacement {
+    #[default]
+    Random,
+    // Only where no live cell is within margin of the entity, trying random spots
+    // first and then scanning the whole world
+    Empty {
+        margin: isize,
+        #[serde(default = "default_attempts")]
+        attempts: usize,
+        #[serde(default)]
+        on_collision: CollisionFallback,
+    },
+}
 
-    let ten_millis = time::Duration::from_millis(ENTROPY_LOOP_DELAY_MILLIS);
+// What to do when no free area is found
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionFallback {
+    #[default]
+    Skip,
+    // Stamp over whatever is at the first random spot, as plain random placement does
+    Overwrite,
+}
 
-    loop {
-        let mut sw: Stopwatch = Stopwatch::start_new();
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementOutcome {
+    Random,
+    Free,
+    Fallback,
+    Aimed,
+}
 
-        {
-            let unlocked_data = match rwlock.read() {
-                Err(_) => {
-                    continue;
-                },
-                Ok(data) => data
-            };
+// Where an injected entity landed, so clients can highlight it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct InjectionReport {
+    // Assigned when the report enters the event log
+    pub id: usize,
+    pub generation: usize,
+    // Species id and name, missing for soup patches
+    pub species: Option<usize>,
+    pub name: Option<String>,
+    pub transform: Option<SpeciesTransform>,
+    pub x: isize,
+    pub y: isize,
+    pub w: isize,
+    pub h: isize,
+    pub outcome: PlacementOutcome,
+}
 
-            let universe = &*unlocked_data;
+// What an injection consists of
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EntropyPayload {
+    #[default]
+    Species,
+    // A size x size patch of random cells
+    Soup { size: usize, density: f64 },
+    // Spaceships starting distance cells away, heading into the target area
+    Meteors {
+        count: usize,
+        x: isize,
+        y: isize,
+        w: isize,
+        h: isize,
+        distance: isize,
+    },
+}
 
-            let entity = get_random_entity(universe);
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct EntropyPolicy {
+    // Generations between injections, 0 disables them
+    pub interval: usize,
+    pub rate: EntropyRate,
+    pub selection: EntropySelection,
+    pub placement: EntropyPlacement,
+    pub payload: EntropyPayload,
+}
 
-            match sender.send(entity) {
-                Err(e) => {
-                    eprintln!("{:?}", e);
-                },
-                Ok(_) => {}
+impl Default for EntropyPolicy {
+    fn default() -> EntropyPolicy {
+        EntropyPolicy {
+            interval: ENTROPY_INTERVAL_GENERATIONS,
+            rate: EntropyRate::default(),
+            selection: EntropySelection::default(),
+            placement: EntropyPlacement::default(),
+            payload: EntropyPayload::default(),
+        }
+    }
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_attempts() -> usize {
+    ENTROPY_EMPTY_ATTEMPTS
+}
+
+impl EntropyPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval > ENTROPY_MAX_INTERVAL {
+            return Err(format!("Interval must be at most {}", ENTROPY_MAX_INTERVAL));
+        }
+
+        match self.rate {
+            EntropyRate::Population { min_interval, .. } if min_interval > ENTROPY_MAX_INTERVAL => {
+                return Err(format!("min_interval must be at most {}", ENTROPY_MAX_INTERVAL));
             }
+            _ => {}
         }
 
-        sw.stop();
+        match &self.selection {
+            EntropySelection::Weighted {
+                weights,
+                default_weight,
+            } => {
+                if weights.values().chain([default_weight]).any(|w| !w.is_finite() || *w < 0.0) {
+                    return Err("Weights must be finite and zero or positive".to_string());
+                }
+                let total: f64 = weights.values().chain([default_weight]).sum();
+                if !total.is_finite() || total <= 0.0 {
+                    return Err("Weights must add up to a finite positive total".to_string());
+                }
+            }
+            EntropySelection::Uniform => {}
+        }
 
-        println!("entropy_loop() elapsed [{} ms]", sw.elapsed_ms());
+        match self.placement {
+            EntropyPlacement::Empty { margin, .. } => {
+                // A single cell with its margin must still fit in the world
+                let max_margin = (WORLD_SIZE_X.min(WORLD_SIZE_Y) - 1) / 2;
+                if !(0..=max_margin).contains(&margin) {
+                    return Err(format!("Margin must be 0 to {}", max_margin));
+                }
+            }
+            EntropyPlacement::Random => {}
+        }
 
-        thread::sleep(ten_millis);
+        match self.payload {
+            EntropyPayload::Soup { size, density } => {
+                if size == 0 || size > ENTROPY_MAX_SOUP_SIZE {
+                    return Err(format!("Soup size must be 1 to {}", ENTROPY_MAX_SOUP_SIZE));
+                }
+                if !(0.0..=1.0).contains(&density) {
+                    return Err("Soup density must be between 0 and 1".to_string());
+                }
+            }
+            EntropyPayload::Meteors { count, x, y, w, h, distance } => {
+                if count > ENTROPY_MAX_METEORS {
+                    return Err(format!("At most {} meteors", ENTROPY_MAX_METEORS));
+                }
+                if w <= 0 || h <= 0 || w > WORLD_SIZE_X || h > WORLD_SIZE_Y {
+                    return Err(format!(
+                        "Meteor target must be between 1x1 and {}x{}",
+                        WORLD_SIZE_X, WORLD_SIZE_Y
+                    ));
+                }
+                check_position(x, y)?;
+                if distance < 0 || distance > WORLD_SIZE_X.max(WORLD_SIZE_Y) {
+                    return Err(format!(
+                        "Meteor distance must be 0 to {}",
+                        WORLD_SIZE_X.max(WORLD_SIZE_Y)
+                    ));
+                }
+            }
+            EntropyPayload::Species => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn effective_interval(&self, population: Option<usize>) -> usize {
+        match (&self.rate, population) {
+            (EntropyRate::Population { target, min_interval }, Some(population))
+                if population < *target =>
+            {
+                (self.interval.saturating_mul(population) / target).max(*min_interval)
+            }
+            _ => self.interval,
+        }
+    }
+}
+
+// Inject on the generation schedule of the policy, so a seed replays exactly
+pub fn entropy_step(universe: &mut Universe, time: isize) -> bool {
+    let policy = universe.get_entropy_policy();
+
+    if policy.interval == 0 {
+        return false;
     }
+
+    let population = universe.get_stats().back().map(|s| s.population);
+    let interval = policy.effective_interval(population);
+
+    if universe.get_generation() < universe.get_last_entropy().saturating_add(interval) {
+        return false;
+    }
+
+    !inject_entropy(universe, time).is_empty()
 }
 
-pub fn get_random_entity(universe: &Universe) -> UniversePlane {
-    let species = universe.get_entities();
+pub fn inject_entropy(universe: &mut Universe, time: isize) -> Vec<InjectionReport> {
+    let generation = universe.get_generation();
+    let policy = universe.get_entropy_policy().clone();
+
+    let injections: Vec<InjectionReport> = match policy.payload {
+        EntropyPayload::Species => match get_random_entity(universe, &policy.selection, false) {
+            None => None,
+            Some((species_id, transform, entity)) => {
+                place_randomly(universe, time, &entity, &policy.placement)
+                    .map(|report| with_species(universe, report, species_id, transform))
+            }
+        }
+        .into_iter()
+        .collect(),
+        EntropyPayload::Soup { size, density } => {
+            let entity = random_soup(universe, size, density);
+            place_randomly(universe, time, &entity, &policy.placement)
+                .into_iter()
+                .collect()
+        }
+        EntropyPayload::Meteors {
+            count,
+            x,
+            y,
+            w,
+            h,
+            distance,
+        } => (0..count)
+            .filter_map(|_| inject_meteor(universe, time, &policy.selection, (x, y, w, h), distance))
+            .collect(),
+    };
+
+    // A skipped injection still uses up its slot in the schedule
+    universe.set_last_entropy(generation);
 
-    let i = rand::thread_rng().gen_range(0..species.len());
+    universe.push_entropy_events(injections)
+}
 
-    let entity = species_nop(&species[i]);
+fn with_species(
+    universe: &Universe,
+    report: InjectionReport,
+    species_id: usize,
+    transform: SpeciesTransform,
+) -> InjectionReport {
+    InjectionReport {
+        species: Some(species_id),
+        name: Some(universe.get_species()[species_id].name.clone()),
+        transform: Some(transform),
+        ..report
+    }
+}
+
+fn place_randomly(
+    universe: &mut Universe,
+    time: isize,
+    entity: &UniversePlane,
+    placement: &EntropyPlacement,
+) -> Option<InjectionReport> {
+    let entity_xsize = entity.len() as isize;
+    let entity_ysize = entity[0].len() as isize;
+
+    let random_position = |universe: &mut Universe| {
+        (
+            universe.get_rng().gen_range(MIN_X..=MAX_X),
+            universe.get_rng().gen_range(MIN_Y..=MAX_Y),
+        )
+    };
+
+    let (position, outcome) = match *placement {
+        EntropyPlacement::Random => (random_position(universe), PlacementOutcome::Random),
+        EntropyPlacement::Empty {
+            margin,
+            attempts,
+            on_collision,
+        } => {
+            let (area_w, area_h) = (entity_xsize + 2 * margin, entity_ysize + 2 * margin);
+            let is_free = |universe: &Universe, (x_pos, y_pos): (isize, isize)| {
+                universe.is_area_free(time, x_pos - margin, y_pos - margin, area_w, area_h)
+            };
+
+            let first = random_position(universe);
+            let mut found = match is_free(universe, first) {
+                true => Some(first),
+                false => None,
+            };
+            for _ in 1..attempts {
+                if found.is_some() {
+                    break;
+                }
+                let candidate = random_position(universe);
+                if is_free(universe, candidate) {
+                    found = Some(candidate);
+                }
+            }
+
+            // Scan the torus from the first spot in steps of half the area
+            let (step_x, step_y) = ((area_w / 2).max(1), (area_h / 2).max(1));
+            let mut y_offset = 0;
+            while found.is_none() && y_offset < WORLD_SIZE_Y {
+                let mut x_offset = 0;
+                while found.is_none() && x_offset < WORLD_SIZE_X {
+                    let candidate = (first.0 + x_offset, first.1 + y_offset);
+                    if is_free(universe, candidate) {
+                        found = Some(candidate);
+                    }
+                    x_offset += step_x;
+                }
+                y_offset += step_y;
+            }
+
+            match (found, on_collision) {
+                (Some(position), _) => (position, PlacementOutcome::Free),
+                (None, CollisionFallback::Overwrite) => (first, PlacementOutcome::Fallback),
+                (None, CollisionFallback::Skip) => {
+                    return None;
+                }
+            }
+        }
+    };
+
+    universe.place_entity(time, position.0, position.1, entity, PlacementMode::Clear);
+
+    Some(InjectionReport {
+        id: 0,
+        generation: universe.get_generation(),
+        species: None,
+        name: None,
+        transform: None,
+        x: position.0,
+        y: position.1,
+        w: entity_xsize,
+        h: entity_ysize,
+        outcome,
+    })
+}
+
+fn inject_meteor(
+    universe: &mut Universe,
+    time: isize,
+    selection: &EntropySelection,
+    target: (isize, isize, isize, isize),
+    distance: isize,
+) -> Option<InjectionReport> {
+    let (species_id, transform, entity) = get_random_entity(universe, selection, true)?;
+
+    // The direction depends on the orientation picked, so classify this one
+    let (dx, dy) = match classify_pattern(&entity).class {
+        PatternClass::Spaceship { dx, dy, .. } => (dx, dy),
+        _ => {
+            return None;
+        }
+    };
+
+    let (x, y, w, h) = target;
+    let (target_x, target_y) = (
+        x + universe.get_rng().gen_range(0..w),
+        y + universe.get_rng().gen_range(0..h),
+    );
+
+    // Periods needed to cover the distance along the fastest axis
+    let periods = distance / dx.abs().max(dy.abs()).max(1);
+    let (x_pos, y_pos) = (target_x - dx * periods, target_y - dy * periods);
+
+    universe.place_entity(time, x_pos, y_pos, &entity, PlacementMode::Clear);
+
+    let report = InjectionReport {
+        id: 0,
+        generation: universe.get_generation(),
+        species: None,
+        name: None,
+        transform: None,
+        x: x_pos,
+        y: y_pos,
+        w: entity.len() as isize,
+        h: entity[0].len() as isize,
+        outcome: PlacementOutcome::Aimed,
+    };
+
+    Some(with_species(universe, report, species_id, transform))
+}
+
+fn random_soup(universe: &mut Universe, size: usize, density: f64) -> UniversePlane {
+    let mut entity: UniversePlane = vec![vec![CELL_DEATH; size]; size];
+
+    for column in entity.iter_mut() {
+        for cell in column.iter_mut() {
+            if universe.get_rng().gen_bool(density) {
+                *cell = CELL_LIVE;
+            }
+        }
+    }
 
     entity
 }
+
+fn species_weight(species: &Species, selection: &EntropySelection) -> f64 {
+    match selection {
+        EntropySelection::Uniform => 1.0,
+        EntropySelection::Weighted {
+            weights,
+            default_weight,
+        } => *weights.get(&species.name).unwrap_or(default_weight),
+    }
+}
+
+fn is_spaceship(species: &Species) -> bool {
+    match &species.metadata.classification {
+        Some(report) => matches!(report.class, PatternClass::Spaceship { .. }),
+        None => false,
+    }
+}
+
+pub fn get_random_entity(
+    universe: &mut Universe,
+    selection: &EntropySelection,
+    spaceships_only: bool,
+) -> Option<(usize, SpeciesTransform, UniversePlane)> {
+    let weights: Vec<f64> = universe
+        .get_species()
+        .iter()
+        .map(|species| match !spaceships_only || is_spaceship(species) {
+            true => species_weight(species, selection),
+            false => 0.0,
+        })
+        .collect();
+
+    let total: f64 = weights.iter().sum();
+
+    // Many species at a large default weight can still add up past f64
+    if !total.is_finite() || total <= 0.0 {
+        return None;
+    }
+
+    // Pick the species first so symmetric patterns are not underrepresented
+    // Rounding can leave the pick past the running sum, it then lands on the last weighted species
+    let mut pick = universe.get_rng().gen_range(0.0..total);
+    let mut i = 0;
+    for (index, weight) in weights.iter().enumerate() {
+        if *weight <= 0.0 {
+            continue;
+        }
+        i = index;
+        if pick < *weight {
+            break;
+        }
+        pick -= weight;
+    }
+
+    let orientations_count = universe.get_species()[i].orientations.len();
+    let j = universe.get_rng().gen_range(0..orientations_count);
+
+    let (transform, entity) = &universe.get_species()[i].orientations[j];
+
+    Some((i, *transform, species_nop(entity)))
+}
@@ -0,0 +1,83 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+use std::sync::PoisonError;
+
+// The one error type of the API, rendered as {"error": "bad_request", "message": "..."}
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    NotAcceptable(String),
+    // The details go to the log, clients only see a generic message
+    Internal(String),
+}
+
+#[derive(Serialize, Debug)]
+pub struct ApiErrorBody<'a> {
+    pub error: &'a str,
+    pub message: &'a str,
+}
+
+impl ApiError {
+    fn get_code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::NotAcceptable(_) => "not_acceptable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    fn get_message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::NotFound(message)
+            | ApiError::NotAcceptable(message) => message.as_str(),
+            ApiError::Internal(_) => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Internal(details) => write!(f, "{}: {}", self.get_code(), details),
+            _ => write!(f, "{}: {}", self.get_code(), self.get_message()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let ApiError::Internal(_) = self {
+            eprintln!("{}", self);
+        }
+
+        HttpResponse::build(self.status_code()).json(ApiErrorBody {
+            error: self.get_code(),
+            message: self.get_message(),
+        })
+    }
+}
+
+// A poisoned lock means the engine thread panicked while holding the universe
+impl<T> From<PoisonError<T>> for ApiError {
+    fn from(error: PoisonError<T>) -> ApiError {
+        ApiError::Internal(error.to_string())
+    }
+}
+
+// Rejected paths, query strings and JSON bodies
+pub fn extractor_error(error: impl fmt::Display) -> actix_web::Error {
+    ApiError::BadRequest(error.to_string()).into()
+}
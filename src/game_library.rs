@@ -0,0 +1,80 @@
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{
+    path::PathBuf,
+    sync::{mpsc::channel, Arc, RwLock},
+    thread, time,
+};
+use stopwatch::Stopwatch;
+
+use crate::game_constants::LIBRARY_RELOAD_DELAY_MILLIS;
+use crate::game_species::load_species_library;
+use crate::game_universe::Universe;
+
+pub fn load_library(rwlock: &Arc<RwLock<Universe>>, paths: &[PathBuf]) {
+    // Parse outside the lock so the engine keeps running meanwhile
+    let (species, report) = load_species_library(paths);
+
+    match rwlock.write() {
+        Err(e) => {
+            eprintln!("{:?}", e);
+        },
+        Ok(mut unlocked_data) => {
+            unlocked_data.set_species(species, report);
+        }
+    }
+}
+
+pub fn library_loop(rwlock: Arc<RwLock<Universe>>, paths: Vec<PathBuf>) {
+    let (sender, receiver) = channel::<notify::Result<Event>>();
+
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return;
+        },
+        Ok(watcher) => watcher
+    };
+
+    for path in &paths {
+        match watcher.watch(path, RecursiveMode::Recursive) {
+            Err(e) => {
+                eprintln!("Not watching {}: {:?}", path.display(), e);
+            },
+            Ok(_) => {
+                println!("Watching species: {}", path.display());
+            }
+        }
+    }
+
+    let reload_delay = time::Duration::from_millis(LIBRARY_RELOAD_DELAY_MILLIS);
+
+    loop {
+        match receiver.recv() {
+            Err(_) => {
+                return;
+            },
+            Ok(Err(e)) => {
+                eprintln!("{:?}", e);
+                continue;
+            },
+            Ok(Ok(event)) => match event.kind {
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {},
+                _ => {
+                    continue;
+                }
+            }
+        }
+
+        // Let a burst of events from a single save settle before reloading
+        thread::sleep(reload_delay);
+        while receiver.try_recv().is_ok() {}
+
+        let mut sw: Stopwatch = Stopwatch::start_new();
+
+        load_library(&rwlock, &paths);
+
+        sw.stop();
+
+        println!("library_loop() elapsed [{} ms]", sw.elapsed_ms());
+    }
+}
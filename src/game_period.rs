@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use crate::game_constants::CYCLE_HISTORY_SIZE;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StableAction {
+    // Only report the cycle
+    #[default]
+    None,
+    // Inject an extra random species
+    Entropy,
+    // Clear the world and start over
+    Reset,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldCycle {
+    pub period: usize,
+    // First generation of the repeating sequence
+    pub since: usize,
+}
+
+pub struct CycleDetector {
+    seen: HashMap<u64, usize>,
+    order: VecDeque<u64>,
+    cycle: Option<WorldCycle>,
+}
+
+// Zobrist key of a cell, derived with splitmix64 so no table is needed
+#[inline]
+pub fn zobrist_key(index: usize) -> u64 {
+    let mut z = (index as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl CycleDetector {
+    pub fn new() -> CycleDetector {
+        CycleDetector {
+            seen: HashMap::with_capacity(CYCLE_HISTORY_SIZE),
+            order: VecDeque::with_capacity(CYCLE_HISTORY_SIZE),
+            cycle: None,
+        }
+    }
+
+    pub fn observe(&mut self, generation: usize, hash: u64) -> Option<WorldCycle> {
+        self.cycle = match self.seen.get(&hash) {
+            None => None,
+            Some(&first) => match self.cycle {
+                // Keep the start of a cycle that is still going on
+                Some(cycle) if cycle.period == generation - first => Some(cycle),
+                _ => Some(WorldCycle {
+                    period: generation - first,
+                    since: first,
+                }),
+            },
+        };
+
+        if self.order.len() == CYCLE_HISTORY_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        if self.seen.insert(hash, generation).is_some() {
+            self.order.retain(|h| *h != hash);
+        }
+        self.order.push_back(hash);
+
+        self.cycle
+    }
+
+    #[inline]
+    pub fn get_cycle(&self) -> Option<WorldCycle> {
+        self.cycle
+    }
+
+    pub fn clear(&mut self) {
+        self.seen.clear();
+        self.order.clear();
+        self.cycle = None;
+    }
+}
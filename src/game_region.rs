@@ -0,0 +1,128 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::game_constants::{UniversePlane, CELL_LIVE, STREAM_FRAME_KEYFRAME};
+use crate::game_universe::WorldBounds;
+
+// How a region is laid out in JSON, binary is always a bitmap
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionLayout {
+    // Live cell coordinates
+    #[default]
+    Cells,
+    // Base64 of the bit-packed bitmap, as in the binary body
+    Bitmap,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RegionQuery {
+    pub x: isize,
+    pub y: isize,
+    pub w: isize,
+    pub h: isize,
+    #[serde(default)]
+    pub layout: RegionLayout,
+}
+
+impl RegionQuery {
+    pub fn get_bounds(&self) -> WorldBounds {
+        WorldBounds {
+            x: self.x,
+            y: self.y,
+            w: self.w,
+            h: self.h,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegionCells {
+    pub generation: usize,
+    pub x: isize,
+    pub y: isize,
+    pub w: isize,
+    pub h: isize,
+    pub population: usize,
+    // World coordinates, x then y
+    pub cells: Vec<(isize, isize)>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegionBitmap {
+    pub generation: usize,
+    pub x: isize,
+    pub y: isize,
+    pub w: isize,
+    pub h: isize,
+    pub population: usize,
+    // Row-major, least significant bit first
+    pub bitmap: String,
+}
+
+pub fn region_cells(generation: usize, bounds: &WorldBounds, world: &UniversePlane) -> RegionCells {
+    let mut cells: Vec<(isize, isize)> = Vec::new();
+
+    for y_index in 0..bounds.h {
+        for x_index in 0..bounds.w {
+            if world[x_index as usize][y_index as usize] == CELL_LIVE {
+                cells.push((bounds.x + x_index, bounds.y + y_index));
+            }
+        }
+    }
+
+    RegionCells {
+        generation,
+        x: bounds.x,
+        y: bounds.y,
+        w: bounds.w,
+        h: bounds.h,
+        population: cells.len(),
+        cells,
+    }
+}
+
+pub fn region_bitmap(generation: usize, bounds: &WorldBounds, world: &UniversePlane) -> RegionBitmap {
+    let bits = pack_plane(bounds, world);
+
+    RegionBitmap {
+        generation,
+        x: bounds.x,
+        y: bounds.y,
+        w: bounds.w,
+        h: bounds.h,
+        population: bits.iter().map(|byte| byte.count_ones() as usize).sum(),
+        bitmap: STANDARD.encode(&bits),
+    }
+}
+
+// Little endian header, u8 kind = 1, u64 generation, i32 x, i32 y, u16 w, u16 h, then the bitmap
+pub fn region_binary(generation: usize, bounds: &WorldBounds, world: &UniversePlane) -> Vec<u8> {
+    let bits = pack_plane(bounds, world);
+
+    let mut frame: Vec<u8> = Vec::with_capacity(21 + bits.len());
+    frame.push(STREAM_FRAME_KEYFRAME);
+    frame.extend_from_slice(&(generation as u64).to_le_bytes());
+    frame.extend_from_slice(&(bounds.x as i32).to_le_bytes());
+    frame.extend_from_slice(&(bounds.y as i32).to_le_bytes());
+    frame.extend_from_slice(&(bounds.w as u16).to_le_bytes());
+    frame.extend_from_slice(&(bounds.h as u16).to_le_bytes());
+    frame.extend_from_slice(&bits);
+
+    frame
+}
+
+fn pack_plane(bounds: &WorldBounds, world: &UniversePlane) -> Vec<u8> {
+    let mut bits = vec![0u8; ((bounds.w * bounds.h) as usize).div_ceil(8)];
+
+    for (x_index, column) in world.iter().enumerate().take(bounds.w as usize) {
+        for (y_index, cell) in column.iter().enumerate().take(bounds.h as usize) {
+            if *cell == CELL_LIVE {
+                let bit = y_index * bounds.w as usize + x_index;
+                bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+    }
+
+    bits
+}
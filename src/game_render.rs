@@ -0,0 +1,659 @@
+use image::{codecs::jpeg::JpegEncoder, ColorType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+use crate::game_constants::{
+    UniversePlane, CELL_LIVE, CELL_SIZE, RENDER_AGE_SATURATION, RENDER_DEFAULT_QUALITY,
+    RENDER_MAX_PIXELS, RENDER_MAX_SCALE, RENDER_MAX_SHADES, RENDER_MAX_ZOOM_OUT,
+};
+
+// Per-cell values between 0 and 1 the renderers color, indexed [x][y] like UniversePlane
+pub type RenderPlane = Vec<Vec<f32>>;
+
+// Named color sets, explicit fg, bg and grid_color win over them
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderTheme {
+    #[default]
+    Light,
+    Dark,
+    // Black on white with a light grid
+    Print,
+    Contrast,
+}
+
+impl RenderTheme {
+    // Foreground, background and grid colors
+    fn get_colors(&self) -> (RenderColor, RenderColor, RenderColor) {
+        match self {
+            RenderTheme::Light => (
+                RenderColor([0, 0, 0]),
+                RenderColor([255, 255, 255]),
+                RenderColor([220, 220, 220]),
+            ),
+            RenderTheme::Dark => (
+                RenderColor([124, 252, 0]),
+                RenderColor([24, 24, 27]),
+                RenderColor([52, 52, 56]),
+            ),
+            RenderTheme::Print => (
+                RenderColor([0, 0, 0]),
+                RenderColor([255, 255, 255]),
+                RenderColor([200, 200, 200]),
+            ),
+            RenderTheme::Contrast => (
+                RenderColor([255, 255, 0]),
+                RenderColor([0, 0, 0]),
+                RenderColor([96, 96, 96]),
+            ),
+        }
+    }
+
+    fn get_grid(&self) -> bool {
+        *self == RenderTheme::Print
+    }
+}
+
+// What the color of a cell stands for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    // Live cells in fg, zoomed out blocks shaded by density
+    #[default]
+    Cells,
+    // Live cells from orange when born to blue once they have lived RENDER_AGE_SATURATION generations
+    Age,
+    // State changes over the history, still lifes cool, oscillators and chaos from red to yellow
+    Heat,
+}
+
+impl RenderMode {
+    // Color ramp over values from 0 to 1, value 0 is always the background
+    fn get_stops(&self) -> &'static [(f32, [u8; 3])] {
+        match self {
+            RenderMode::Cells => &[],
+            RenderMode::Age => &[
+                (0.1, [255, 80, 0]),
+                (0.4, [230, 200, 0]),
+                (0.7, [0, 170, 90]),
+                (1.0, [30, 70, 200]),
+            ],
+            RenderMode::Heat => &[
+                (0.1, [70, 110, 200]),
+                (0.2, [140, 0, 0]),
+                (0.6, [255, 110, 0]),
+                (1.0, [255, 235, 100]),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct RenderColor(pub [u8; 3]);
+
+// rrggbb or #rrggbb, the # has to be sent as %23 in a query string
+impl TryFrom<String> for RenderColor {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<RenderColor, String> {
+        let hex = text.strip_prefix('#').unwrap_or(text.as_str());
+
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid color {:?}, expected rrggbb", text));
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string());
+
+        Ok(RenderColor([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+impl From<RenderColor> for String {
+    fn from(color: RenderColor) -> String {
+        color.to_hex()
+    }
+}
+
+impl RenderColor {
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0[0], self.0[1], self.0[2])
+    }
+
+    // Mix of fg over self for a density between 0 and 1
+    pub fn blend(&self, fg: &RenderColor, density: f32) -> RenderColor {
+        let mix = |i: usize| {
+            (self.0[i] as f32 + (fg.0[i] as f32 - self.0[i] as f32) * density).round() as u8
+        };
+
+        RenderColor([mix(0), mix(1), mix(2)])
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl RenderFormat {
+    pub fn get_content_type(&self) -> &'static str {
+        match self {
+            RenderFormat::Png => "image/png",
+            RenderFormat::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+// PNG deflate effort
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+impl RenderCompression {
+    fn get_png(&self) -> png::Compression {
+        match self {
+            RenderCompression::Fast => png::Compression::Fast,
+            RenderCompression::Default => png::Compression::Default,
+            RenderCompression::Best => png::Compression::Best,
+        }
+    }
+}
+
+// How /getimage encodes the pixels, read next to RenderQuery from the same query string
+#[derive(Deserialize, Debug, Default)]
+pub struct ImageQuery {
+    pub format: Option<RenderFormat>,
+    // Indexed PNG by default, palette=false writes 24-bit RGB
+    pub palette: Option<bool>,
+    pub compression: Option<RenderCompression>,
+    // JPEG only, 1 to 100
+    pub quality: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    pub format: RenderFormat,
+    pub palette: bool,
+    pub compression: RenderCompression,
+    pub quality: u8,
+}
+
+impl ImageQuery {
+    pub fn get_options(&self) -> Result<ImageOptions, String> {
+        let quality = self.quality.unwrap_or(RENDER_DEFAULT_QUALITY);
+        if quality == 0 || quality > 100 {
+            return Err("quality must be between 1 and 100".to_string());
+        }
+
+        Ok(ImageOptions {
+            format: self.format.unwrap_or_default(),
+            palette: self.palette.unwrap_or(true),
+            compression: self.compression.unwrap_or_default(),
+            quality,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RenderQuery {
+    // Pixels per cell
+    pub scale: Option<usize>,
+    // Cells per pixel side, each pixel is shaded by the density of its NxN block
+    pub zoom_out: Option<usize>,
+    pub theme: Option<RenderTheme>,
+    pub fg: Option<RenderColor>,
+    pub bg: Option<RenderColor>,
+    pub grid: Option<bool>,
+    pub grid_color: Option<RenderColor>,
+    pub mode: Option<RenderMode>,
+}
+
+#[derive(Debug, Clone, Copy, Hash)]
+pub struct RenderOptions {
+    pub scale: usize,
+    pub zoom_out: usize,
+    pub fg: RenderColor,
+    pub bg: RenderColor,
+    // Only drawn when cells are at least three pixels wide
+    pub grid: Option<RenderColor>,
+    pub mode: RenderMode,
+}
+
+impl RenderQuery {
+    pub fn get_options(&self, w: isize, h: isize) -> Result<RenderOptions, String> {
+        let theme = self.theme.unwrap_or_default();
+        let (fg, bg, grid_color) = theme.get_colors();
+
+        let (scale, zoom_out) = match (self.scale, self.zoom_out) {
+            (Some(_), Some(_)) => return Err("Use either scale or zoom_out".to_string()),
+            (Some(scale), None) => (scale, 1),
+            (None, Some(zoom_out)) => (1, zoom_out),
+            (None, None) => (CELL_SIZE, 1),
+        };
+
+        if scale == 0 || scale > RENDER_MAX_SCALE {
+            return Err(format!("scale must be between 1 and {}", RENDER_MAX_SCALE));
+        }
+        if zoom_out == 0 || zoom_out > RENDER_MAX_ZOOM_OUT {
+            return Err(format!("zoom_out must be between 1 and {}", RENDER_MAX_ZOOM_OUT));
+        }
+
+        let options = RenderOptions {
+            scale,
+            zoom_out,
+            fg: self.fg.unwrap_or(fg),
+            bg: self.bg.unwrap_or(bg),
+            grid: match self.grid.unwrap_or(theme.get_grid()) && scale >= 3 {
+                true => Some(self.grid_color.unwrap_or(grid_color)),
+                false => None,
+            },
+            mode: self.mode.unwrap_or_default(),
+        };
+
+        let (width, height) = options.get_size(w as usize, h as usize);
+        if width * height > RENDER_MAX_PIXELS {
+            return Err(format!(
+                "Image {}x{} is larger than {} pixels, lower scale or use zoom_out",
+                width, height, RENDER_MAX_PIXELS
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+impl RenderOptions {
+    // Image size in pixels for a region of xsize x ysize cells
+    pub fn get_size(&self, xsize: usize, ysize: usize) -> (usize, usize) {
+        (
+            xsize.div_ceil(self.zoom_out) * self.scale,
+            ysize.div_ceil(self.zoom_out) * self.scale,
+        )
+    }
+}
+
+// Live cells as 1 and dead ones as 0, what RenderMode::Cells draws
+pub fn cell_values(world: &UniversePlane) -> RenderPlane {
+    world
+        .iter()
+        .map(|column| column.iter().map(|cell| (*cell == CELL_LIVE) as u8 as f32).collect())
+        .collect()
+}
+
+// Dead cells 0, newborn cells 0.1 up to 1 on a log scale once RENDER_AGE_SATURATION old
+pub fn age_values(ages: &[Vec<u32>]) -> RenderPlane {
+    let saturation = (RENDER_AGE_SATURATION as f32).ln();
+
+    ages.iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|age| match *age {
+                    0 => 0.0,
+                    age => (0.1 + 0.9 * (age as f32).ln() / saturation).min(1.0),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Cells that never changed are 0, or 0.1 when alive, the others 0.2 up to 1 when they changed every step
+pub fn heat_values(world: &UniversePlane, changes: &[Vec<u32>], steps: usize) -> RenderPlane {
+    world
+        .iter()
+        .zip(changes)
+        .map(|(cells, column)| {
+            cells
+                .iter()
+                .zip(column)
+                .map(|(cell, count)| match (*count, *cell == CELL_LIVE) {
+                    (0, true) => 0.1,
+                    (0, false) => 0.0,
+                    (count, _) => 0.2 + 0.8 * count as f32 / steps.max(1) as f32,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Mean value under every output block, the values themselves when not zoomed out
+pub fn shade_plane(values: &[Vec<f32>], zoom_out: usize) -> RenderPlane {
+    let xsize = values.len();
+    let ysize = values[0].len();
+
+    let mut shades = vec![vec![0f32; ysize.div_ceil(zoom_out)]; xsize.div_ceil(zoom_out)];
+
+    for a in 0..xsize {
+        for b in 0..ysize {
+            shades[a / zoom_out][b / zoom_out] += values[a][b];
+        }
+    }
+
+    // Blocks on the right and bottom edges may be partial
+    for (i, column) in shades.iter_mut().enumerate() {
+        let block_w = zoom_out.min(xsize - i * zoom_out);
+        for (j, shade) in column.iter_mut().enumerate() {
+            let block_h = zoom_out.min(ysize - j * zoom_out);
+            *shade /= (block_w * block_h) as f32;
+        }
+    }
+
+    shades
+}
+
+// Horizontal runs of equal shade become one subpath each, all runs of a color share one path
+pub fn render_svg(values: &[Vec<f32>], options: &RenderOptions) -> String {
+    let shades = shade_plane(values, options.zoom_out);
+    let (width, height) = options.get_size(values.len(), values[0].len());
+    let scale = options.scale;
+
+    let mut svgcontent = String::new();
+    svgcontent.push_str("<?xml version='1.0' encoding='UTF-8'?>");
+    svgcontent.push_str(
+        "<!DOCTYPE svg PUBLIC '-//W3C//DTD SVG 1.1//EN' 'http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd'>"
+    );
+    let _ = write!(
+        svgcontent,
+        "<svg xmlns='http://www.w3.org/2000/svg' version='1.1' width='{}' height='{}' shape-rendering='crispEdges'>",
+        width, height
+    );
+    let _ = write!(
+        svgcontent,
+        "<rect width='{}' height='{}' fill='{}'/>",
+        width,
+        height,
+        options.bg.to_hex()
+    );
+
+    // Path data per palette index, each run starts with a move relative to the start of the previous one
+    let mut paths: BTreeMap<u8, (String, (usize, usize))> = BTreeMap::new();
+
+    let levels = get_levels(options);
+    let palette = get_palette(options);
+
+    let columns = shades.len();
+    let rows = shades[0].len();
+
+    for b in 0..rows {
+        let row: Vec<u8> = shades.iter().map(|column| get_index(column[b], levels)).collect();
+
+        let mut a = 0;
+        while a < columns {
+            let index = row[a];
+            if index == 0 {
+                a += 1;
+                continue;
+            }
+
+            let run_start = a;
+            while a < columns && row[a] == index {
+                a += 1;
+            }
+
+            let (path, last) = paths.entry(index).or_insert_with(|| (String::new(), (0, 0)));
+            let (x, y) = (run_start * scale, b * scale);
+            let _ = write!(
+                path,
+                "m{} {}h{}v{}h-{}z",
+                x as isize - last.0 as isize,
+                y as isize - last.1 as isize,
+                (a - run_start) * scale,
+                scale,
+                (a - run_start) * scale
+            );
+            *last = (x, y);
+        }
+    }
+
+    for (index, (path, _)) in paths {
+        let _ = write!(
+            svgcontent,
+            "<path fill='{}' d='{}'/>",
+            palette[index as usize].to_hex(),
+            path
+        );
+    }
+
+    if let Some(grid) = options.grid {
+        let mut path = String::new();
+        for a in 0..=columns {
+            let _ = write!(path, "M{} 0v{}", a * scale, height);
+        }
+        for b in 0..=rows {
+            let _ = write!(path, "M0 {}h{}", b * scale, width);
+        }
+        let _ = write!(
+            svgcontent,
+            "<path fill='none' stroke='{}' stroke-width='1' d='{}'/>",
+            grid.to_hex(),
+            path
+        );
+    }
+
+    svgcontent.push_str("</svg>");
+
+    svgcontent
+}
+
+// Pixels are written as palette indices, a cell row is built once and repeated scale times
+pub fn render_image(
+    values: &[Vec<f32>],
+    options: &RenderOptions,
+    image: &ImageOptions,
+) -> Result<Vec<u8>, String> {
+    let shades = shade_plane(values, options.zoom_out);
+    let (width, height) = options.get_size(values.len(), values[0].len());
+    let palette = get_palette(options);
+
+    match image.format {
+        RenderFormat::Png => encode_png(&shades, options, image, &palette, width, height),
+        RenderFormat::Jpeg => encode_jpeg(&shades, options, image, &palette, width, height),
+    }
+}
+
+// bg, the shades of fg or the mode ramp up to value 1, then the grid color
+pub fn get_palette(options: &RenderOptions) -> Vec<RenderColor> {
+    let levels = get_levels(options);
+
+    let mut palette: Vec<RenderColor> = (0..=levels)
+        .map(|level| get_color(options, level as f32 / levels as f32))
+        .collect();
+
+    if let Some(grid) = options.grid {
+        palette.push(grid);
+    }
+
+    palette
+}
+
+fn get_color(options: &RenderOptions, value: f32) -> RenderColor {
+    if options.mode == RenderMode::Cells {
+        return options.bg.blend(&options.fg, value);
+    }
+
+    let mut previous = (0.0, options.bg);
+    for (stop, color) in options.mode.get_stops() {
+        let color = RenderColor(*color);
+        if value <= *stop {
+            return previous.1.blend(&color, (value - previous.0) / (stop - previous.0));
+        }
+        previous = (*stop, color);
+    }
+
+    previous.1
+}
+
+// Distinct shades a block can take, capped so the palette fits in a byte
+fn get_levels(options: &RenderOptions) -> usize {
+    match options.mode {
+        RenderMode::Cells => (options.zoom_out * options.zoom_out).min(RENDER_MAX_SHADES),
+        RenderMode::Age | RenderMode::Heat => RENDER_MAX_SHADES,
+    }
+}
+
+fn get_index(shade: f32, levels: usize) -> u8 {
+    (shade * levels as f32).round() as u8
+}
+
+// Calls write with every distinct pixel row of the image, as palette indices, and how many times it repeats
+pub fn pixel_rows<F>(shades: &[Vec<f32>], options: &RenderOptions, width: usize, mut write: F) -> Result<(), String>
+where
+    F: FnMut(&[u8], usize) -> Result<(), String>,
+{
+    let levels = get_levels(options);
+    let scale = options.scale;
+
+    // Gridlines take the first pixel row and column of every cell
+    let grid_index = (levels + 1) as u8;
+    let grid_row = vec![grid_index; width];
+
+    let mut row = vec![0u8; width];
+
+    for b in 0..shades[0].len() {
+        for (a, column) in shades.iter().enumerate() {
+            let index = get_index(column[b], levels);
+            row[a * scale..(a + 1) * scale].fill(index);
+            if options.grid.is_some() {
+                row[a * scale] = grid_index;
+            }
+        }
+
+        match options.grid {
+            Some(_) => {
+                write(&grid_row, 1)?;
+                write(&row, scale - 1)?;
+            },
+            None => write(&row, scale)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn encode_png(
+    shades: &[Vec<f32>],
+    options: &RenderOptions,
+    image: &ImageOptions,
+    palette: &[RenderColor],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, String> {
+    let mut buffer: Vec<u8> = Vec::new();
+
+    let depth = get_depth(palette);
+
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, width as u32, height as u32);
+        encoder.set_compression(image.compression.get_png());
+
+        match image.palette {
+            true => set_png_palette(&mut encoder, palette),
+            false => {
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+            },
+        }
+
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        let mut stream = writer.stream_writer().map_err(|e| e.to_string())?;
+
+        let mut packed: Vec<u8> = Vec::new();
+        pixel_rows(shades, options, width, |row, repeat| {
+            match image.palette {
+                true => pack_row(row, depth, &mut packed),
+                false => rgb_row(row, palette, &mut packed),
+            }
+            for _ in 0..repeat {
+                stream.write_all(&packed).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        })?;
+
+        stream.finish().map_err(|e| e.to_string())?;
+    }
+
+    Ok(buffer)
+}
+
+fn encode_jpeg(
+    shades: &[Vec<f32>],
+    options: &RenderOptions,
+    image: &ImageOptions,
+    palette: &[RenderColor],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, String> {
+    let mut pixels: Vec<u8> = Vec::with_capacity(width * height * 3);
+
+    let mut packed: Vec<u8> = Vec::new();
+    pixel_rows(shades, options, width, |row, repeat| {
+        rgb_row(row, palette, &mut packed);
+        for _ in 0..repeat {
+            pixels.extend_from_slice(&packed);
+        }
+        Ok(())
+    })?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut buffer, image.quality);
+    if let Err(e) = encoder.encode(&pixels, width as u32, height as u32, ColorType::Rgb8) {
+        return Err(e.to_string());
+    }
+
+    Ok(buffer)
+}
+
+// Smallest bit depth that holds the palette, two colors make a 1-bit image
+pub fn get_depth(palette: &[RenderColor]) -> usize {
+    match palette.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+pub fn set_png_palette<W: std::io::Write>(encoder: &mut png::Encoder<W>, palette: &[RenderColor]) {
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(match get_depth(palette) {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    });
+    encoder.set_palette(palette.iter().flat_map(|color| color.0).collect::<Vec<u8>>());
+    // Filters rarely help indexed pixels
+    encoder.set_filter(png::FilterType::NoFilter);
+}
+
+// Indices packed most significant bits first, as PNG wants them
+pub fn pack_row(row: &[u8], depth: usize, packed: &mut Vec<u8>) {
+    packed.clear();
+    packed.resize((row.len() * depth).div_ceil(8), 0);
+
+    match depth {
+        8 => packed.copy_from_slice(row),
+        _ => {
+            for (i, index) in row.iter().enumerate() {
+                let bit = i * depth;
+                packed[bit / 8] |= index << (8 - depth - bit % 8);
+            }
+        },
+    }
+}
+
+fn rgb_row(row: &[u8], palette: &[RenderColor], packed: &mut Vec<u8>) {
+    packed.clear();
+    packed.extend(row.iter().flat_map(|index| palette[*index as usize].0));
+}
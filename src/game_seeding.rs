@@ -0,0 +1,240 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use stopwatch::Stopwatch;
+
+use crate::game_constants::{
+    CELL_DEATH, CELL_LIVE, MAX_X, MAX_Y, MIN_X, MIN_Y, SEEDING_TILE_GAP, UniversePlane,
+    WORLD_SIZE_X, WORLD_SIZE_Y,
+};
+use crate::game_species::{load_species_file, species_transform, SpeciesTransform};
+use crate::game_universe::{check_position, PlacementMode, Universe, WorldBounds};
+
+// A library species at a given position
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SeedPattern {
+    pub name: String,
+    pub x: isize,
+    pub y: isize,
+    #[serde(default)]
+    pub transform: SpeciesTransform,
+}
+
+// NAME@X,Y as given on the command line
+impl FromStr for SeedPattern {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<SeedPattern, String> {
+        let (name, position) = text
+            .split_once('@')
+            .ok_or_else(|| format!("Expected NAME@X,Y, got {}", text))?;
+        let (x, y) = position
+            .split_once(',')
+            .ok_or_else(|| format!("Expected NAME@X,Y, got {}", text))?;
+
+        Ok(SeedPattern {
+            name: name.to_string(),
+            x: x.trim().parse::<isize>().map_err(|e| format!("{}: {}", x, e))?,
+            y: y.trim().parse::<isize>().map_err(|e| format!("{}: {}", y, e))?,
+            transform: SpeciesTransform::Nop,
+        })
+    }
+}
+
+// What the world holds at generation zero
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorldSeeding {
+    #[default]
+    Empty,
+    // Every cell is live with this probability, drawn from the simulation seed
+    Random { density: f64 },
+    // Library species at given positions
+    Patterns { patterns: Vec<SeedPattern> },
+    // A library species repeated over an area, the whole world when missing
+    Tile {
+        name: String,
+        #[serde(default = "default_tile_gap")]
+        gap: isize,
+        #[serde(default)]
+        area: Option<WorldBounds>,
+    },
+    // A .cells, .rle or .lif pattern, or a /gettext snapshot saved as .txt
+    File {
+        path: PathBuf,
+        #[serde(default)]
+        x: isize,
+        #[serde(default)]
+        y: isize,
+    },
+}
+
+fn default_tile_gap() -> isize {
+    SEEDING_TILE_GAP
+}
+
+impl WorldSeeding {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            WorldSeeding::Random { density } if !(0.0..=1.0).contains(density) => {
+                Err("Random density must be between 0 and 1".to_string())
+            }
+            WorldSeeding::Patterns { patterns } => {
+                patterns.iter().try_for_each(|pattern| check_position(pattern.x, pattern.y))
+            }
+            WorldSeeding::Tile { gap, .. } if !(0..=WORLD_SIZE_X.max(WORLD_SIZE_Y)).contains(gap) => {
+                Err(format!("Tile gap must be 0 to {}", WORLD_SIZE_X.max(WORLD_SIZE_Y)))
+            }
+            WorldSeeding::Tile { area: Some(area), .. } => {
+                if area.w <= 0 || area.h <= 0 || area.w > WORLD_SIZE_X || area.h > WORLD_SIZE_Y {
+                    return Err(format!(
+                        "Tile area must be between 1x1 and {}x{}",
+                        WORLD_SIZE_X, WORLD_SIZE_Y
+                    ));
+                }
+                check_position(area.x, area.y)
+            }
+            WorldSeeding::File { x, y, .. } => check_position(*x, *y),
+            _ => Ok(()),
+        }
+    }
+
+    // Every species named is in the library, so seed_world will not fail halfway
+    pub fn check_species(&self, universe: &Universe) -> Result<(), String> {
+        match self {
+            WorldSeeding::Patterns { patterns } => patterns
+                .iter()
+                .try_for_each(|pattern| find_entity(universe, &pattern.name, pattern.transform).map(|_| ())),
+            WorldSeeding::Tile { name, .. } => find_entity(universe, name, SpeciesTransform::Nop).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Fill the current plane of a freshly reset universe, the species library must be loaded
+pub fn seed_world(universe: &mut Universe) -> Result<(), String> {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let seeding = universe.get_seeding().clone();
+    let time = universe.get_current_time();
+
+    match &seeding {
+        WorldSeeding::Empty => {}
+        WorldSeeding::Random { density } => {
+            for x_pos in MIN_X..=MAX_X {
+                for y_pos in MIN_Y..=MAX_Y {
+                    if universe.get_rng().gen_bool(*density) {
+                        universe.set_cell(time, x_pos, y_pos, 0, 0, CELL_LIVE);
+                    }
+                }
+            }
+        }
+        WorldSeeding::Patterns { patterns } => {
+            for pattern in patterns {
+                let entity = find_entity(universe, &pattern.name, pattern.transform)?;
+                universe.place_entity(time, pattern.x, pattern.y, &entity, PlacementMode::Or);
+            }
+        }
+        WorldSeeding::Tile { name, gap, area } => {
+            let entity = find_entity(universe, name, SpeciesTransform::Nop)?;
+            let area = area.unwrap_or(WorldBounds {
+                x: MIN_X,
+                y: MIN_Y,
+                w: WORLD_SIZE_X,
+                h: WORLD_SIZE_Y,
+            });
+
+            let entity_xsize = entity.len() as isize;
+            let entity_ysize = entity[0].len() as isize;
+
+            // Only whole copies, so tiles never overlap across the torus edge
+            let mut x_off = 0;
+            while x_off + entity_xsize <= area.w {
+                let mut y_off = 0;
+                while y_off + entity_ysize <= area.h {
+                    universe.place_entity(time, area.x + x_off, area.y + y_off, &entity, PlacementMode::Or);
+                    y_off += entity_ysize + gap;
+                }
+                x_off += entity_xsize + gap;
+            }
+        }
+        WorldSeeding::File { path, x, y } => {
+            let entity = load_seed_file(path)?;
+            universe.place_entity(time, *x, *y, &entity, PlacementMode::Overwrite);
+        }
+    }
+
+    sw.stop();
+
+    println!("seed_world() elapsed [{} ms]", sw.elapsed_ms());
+
+    Ok(())
+}
+
+fn find_entity(
+    universe: &Universe,
+    name: &str,
+    transform: SpeciesTransform,
+) -> Result<UniversePlane, String> {
+    match universe.get_species().iter().find(|s| s.name == name) {
+        None => Err(format!("Unknown species {}", name)),
+        Some(species) => Ok(species_transform(species.get_base(), transform)),
+    }
+}
+
+pub fn load_seed_file(path: &Path) -> Result<UniversePlane, String> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("txt") => {
+            let content = read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            snapshot_to_vec(content.as_str())
+        }
+        _ => load_species_file(path)
+            .map(|species| species.get_base().clone())
+            .map_err(|e| format!("{}: {}", path.display(), e)),
+    }
+}
+
+// The /gettext format, X for live cells and one line per column, untrimmed so positions survive
+fn snapshot_to_vec(text: &str) -> Result<UniversePlane, String> {
+    // Sizes are checked before the plane is allocated, a snapshot never outgrows the world
+    let mut lines: Vec<&str> = Vec::new();
+    let mut entity_ysize: usize = 0;
+
+    for line in text.lines() {
+        if lines.len() == WORLD_SIZE_X as usize {
+            return Err(format!("Snapshot has more than {} lines", WORLD_SIZE_X));
+        }
+        let line_size = line.chars().take(WORLD_SIZE_Y as usize + 1).count();
+        if line_size > WORLD_SIZE_Y as usize {
+            return Err(format!(
+                "Snapshot line {} is longer than {} cells",
+                lines.len() + 1,
+                WORLD_SIZE_Y
+            ));
+        }
+        entity_ysize = entity_ysize.max(line_size);
+        lines.push(line);
+    }
+
+    let entity_xsize = lines.len();
+
+    if entity_ysize == 0 {
+        return Err("Empty snapshot".to_string());
+    }
+
+    let mut entity: UniversePlane = vec![vec![CELL_DEATH; entity_ysize]; entity_xsize];
+
+    for (x_index, line) in lines.iter().enumerate() {
+        for (y_index, character) in line.chars().enumerate() {
+            match character {
+                'X' => entity[x_index][y_index] = CELL_LIVE,
+                ' ' => {}
+                _ => return Err(format!("Invalid snapshot character {:?}", character)),
+            }
+        }
+    }
+
+    Ok(entity)
+}
@@ -0,0 +1,206 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::write;
+use std::thread;
+use stopwatch::Stopwatch;
+
+use crate::game_census::{find_cell_islands, tally_islands, CensusEntry};
+use crate::game_classifier::{step_cells, CellSet, PatternClass};
+use crate::game_cli::SoupArgs;
+use crate::game_constants::{
+    SOUP_DENSITY, SOUP_MAX_GENERATIONS, SOUP_MAX_PERIOD, SOUP_MAX_POPULATION,
+    SOUP_RARE_MIN_PERIOD, SOUP_SIZE, SOUP_STABLE_WINDOW,
+};
+
+const GLIDER_CODE: &str = "xq4_153";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SoupFind {
+    pub seed: u64,
+    pub code: String,
+    pub class: PatternClass,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SoupReport {
+    pub first_seed: u64,
+    pub soups: u64,
+    pub elapsed_ms: i64,
+    // Seeds of soups still active when the generation or population limit was hit
+    pub unstabilized: Vec<u64>,
+    pub objects: Vec<CensusEntry>,
+    pub rare: Vec<SoupFind>,
+}
+
+struct SoupResult {
+    seed: u64,
+    stabilized: bool,
+    objects: Vec<CensusEntry>,
+}
+
+pub fn soup_cells(seed: u64) -> CellSet {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut cells = CellSet::new();
+    for x in 0..SOUP_SIZE as isize {
+        for y in 0..SOUP_SIZE as isize {
+            if rng.gen_bool(SOUP_DENSITY) {
+                cells.insert((x, y));
+            }
+        }
+    }
+
+    cells
+}
+
+// The population repeats with a short period over the whole recent window
+fn is_stable(populations: &[usize]) -> bool {
+    if populations.len() < SOUP_STABLE_WINDOW + SOUP_MAX_PERIOD {
+        return false;
+    }
+
+    let start = populations.len() - SOUP_STABLE_WINDOW;
+
+    (1..=SOUP_MAX_PERIOD)
+        .any(|period| (start..populations.len()).all(|i| populations[i] == populations[i - period]))
+}
+
+fn run_soup(seed: u64) -> SoupResult {
+    let mut cells = soup_cells(seed);
+    let mut populations: Vec<usize> = vec![cells.len()];
+    let mut stabilized = false;
+
+    for generation in 1..=SOUP_MAX_GENERATIONS {
+        cells = step_cells(&cells);
+        populations.push(cells.len());
+
+        if cells.len() > SOUP_MAX_POPULATION {
+            break;
+        }
+        if generation % SOUP_STABLE_WINDOW == 0 && is_stable(&populations) {
+            stabilized = true;
+            break;
+        }
+    }
+
+    let objects = match stabilized {
+        true => tally_islands(&find_cell_islands(&cells), &[]),
+        false => Vec::new(),
+    };
+
+    SoupResult {
+        seed,
+        stabilized,
+        objects,
+    }
+}
+
+fn is_rare(entry: &CensusEntry) -> bool {
+    match entry.class {
+        PatternClass::Oscillator { period } => period >= SOUP_RARE_MIN_PERIOD,
+        PatternClass::Spaceship { .. } => entry.code != GLIDER_CODE,
+        _ => false,
+    }
+}
+
+pub fn soup_search(args: &SoupArgs) -> SoupReport {
+    let mut sw: Stopwatch = Stopwatch::start_new();
+
+    let threads = args.threads.max(1) as u64;
+
+    let results: Vec<SoupResult> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|worker| {
+                scope.spawn(move || {
+                    (worker..args.soups)
+                        .step_by(threads as usize)
+                        .map(|index| run_soup(args.seed.wrapping_add(index)))
+                        .collect::<Vec<SoupResult>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Err(e) => {
+                    eprintln!("{:?}", e);
+                    Vec::new()
+                },
+                Ok(results) => results
+            })
+            .collect()
+    });
+
+    let mut unstabilized: Vec<u64> = Vec::new();
+    let mut counts: HashMap<String, CensusEntry> = HashMap::new();
+    let mut rare: Vec<SoupFind> = Vec::new();
+
+    for result in results {
+        if !result.stabilized {
+            unstabilized.push(result.seed);
+        }
+        for entry in result.objects {
+            if is_rare(&entry) {
+                rare.push(SoupFind {
+                    seed: result.seed,
+                    code: entry.code.clone(),
+                    class: entry.class,
+                });
+            }
+            counts
+                .entry(entry.code.clone())
+                .or_insert_with(|| CensusEntry {
+                    count: 0,
+                    ..entry.clone()
+                })
+                .count += entry.count;
+        }
+    }
+
+    unstabilized.sort_unstable();
+    rare.sort_by_key(|find| find.seed);
+
+    let mut objects: Vec<CensusEntry> = counts.into_values().collect();
+    objects.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(&b.code)));
+
+    sw.stop();
+
+    SoupReport {
+        first_seed: args.seed,
+        soups: args.soups,
+        elapsed_ms: sw.elapsed_ms(),
+        unstabilized,
+        objects,
+        rare,
+    }
+}
+
+pub fn soup_main(args: &SoupArgs) -> std::io::Result<()> {
+    println!(
+        "soup_search() {} soups from seed {} on {} threads",
+        args.soups, args.seed, args.threads
+    );
+
+    let report = soup_search(args);
+
+    for entry in report.objects.iter().take(10) {
+        println!("{:>10} {}", entry.count, entry.code);
+    }
+    for find in &report.rare {
+        println!("Rare find: {} [seed {}]", find.code, find.seed);
+    }
+
+    println!(
+        "soup_search() elapsed [{} ms], {} unstabilized, report {}",
+        report.elapsed_ms,
+        report.unstabilized.len(),
+        args.output.display()
+    );
+
+    let content = serde_json::to_string_pretty(&report)?;
+
+    write(&args.output, content)
+}
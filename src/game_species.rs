@@ -1,182 +1,586 @@
-use glob::glob;
-use regex::{RegexBuilder, RegexSetBuilder};
+use glob::{glob, Pattern};
+use regex::{Regex, RegexBuilder, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
 use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::game_classifier::{classify_pattern, PatternReport};
+use crate::game_constants::{
+    CELL_DEATH, CELL_LIVE, DEFAULT_RULE, WORLD_SIZE_X, WORLD_SIZE_Y, UniversePlane,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeciesTransform {
+    #[default]
+    Nop,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipH,
+    FlipV,
+    FlipDiagonal,
+    FlipAntiDiagonal,
+}
+
+pub const SPECIES_TRANSFORMS: [SpeciesTransform; 8] = [
+    SpeciesTransform::Nop,
+    SpeciesTransform::Rotate90,
+    SpeciesTransform::Rotate180,
+    SpeciesTransform::Rotate270,
+    SpeciesTransform::FlipH,
+    SpeciesTransform::FlipV,
+    SpeciesTransform::FlipDiagonal,
+    SpeciesTransform::FlipAntiDiagonal,
+];
+
+// Subgroups of D4 a pattern can be invariant under
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeciesSymmetry {
+    C1,
+    C2,
+    C4,
+    D1Orthogonal,
+    D1Diagonal,
+    D2Orthogonal,
+    D2Diagonal,
+    D4,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SpeciesMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub rule: String,
+    pub comments: Vec<String>,
+    pub width: usize,
+    pub height: usize,
+    pub population: usize,
+    pub period: Option<usize>,
+    pub velocity: Option<String>,
+    pub tags: Vec<String>,
+    pub classification: Option<PatternReport>,
+}
+
+impl Default for SpeciesMetadata {
+    fn default() -> SpeciesMetadata {
+        SpeciesMetadata {
+            title: None,
+            author: None,
+            rule: DEFAULT_RULE.to_string(),
+            comments: Vec::new(),
+            width: 0,
+            height: 0,
+            population: 0,
+            period: None,
+            velocity: None,
+            tags: Vec::new(),
+            classification: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Species {
+    pub name: String,
+    pub symmetry: SpeciesSymmetry,
+    pub metadata: SpeciesMetadata,
+    // Distinct orientations, the first one is always the untransformed base
+    pub orientations: Vec<(SpeciesTransform, UniversePlane)>,
+}
 
-use crate::game_constants::{CELL_DEATH, CELL_LIVE, UniversePlane, UniversePlaneSet};
-use crate::game_universe::Universe;
+#[derive(Deserialize, Debug, Default)]
+pub struct SpeciesQuery {
+    pub q: Option<String>,
+    pub period: Option<usize>,
+    pub rule: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl SpeciesQuery {
+    pub fn matches(&self, species: &Species) -> bool {
+        let metadata = &species.metadata;
+
+        if let Some(q) = &self.q {
+            let q = q.to_lowercase();
+            let found = species.name.to_lowercase().contains(&q)
+                || metadata.title.iter().any(|t| t.to_lowercase().contains(&q))
+                || metadata.author.iter().any(|a| a.to_lowercase().contains(&q))
+                || metadata.comments.iter().any(|c| c.to_lowercase().contains(&q))
+                || metadata.tags.iter().any(|t| t.to_lowercase().contains(&q));
+            if !found {
+                return false;
+            }
+        }
+
+        if self.period.is_some() && self.period != metadata.period {
+            return false;
+        }
 
-fn process_lif(text: &str) -> String {
-    const PATTERN_COMMENT: &'static str = r"^#.*$";
-    const PATTERN_SIZE: &'static str = r"^\s*x\s*=\s*(?P<X>\d*)\s*,\s*y\s*=\s*(?P<Y>\d*).*$";
-    const PATTERN_RLE: &'static str = r"^(\d*[bo\$!])*$";
-    const PATTERN_RLE2: &'static str = r"((?P<rle1>\d*)(?P<rle2>[bo\$!]))";
+        if let Some(rule) = &self.rule {
+            if normalize_rule(rule) != normalize_rule(&metadata.rule) {
+                return false;
+            }
+        }
+
+        if let Some(tag) = &self.tag {
+            if !metadata.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Species {
+    #[inline]
+    pub fn get_base(&self) -> &UniversePlane {
+        &self.orientations[0].1
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SpeciesLoadEntry {
+    pub path: String,
+    pub name: String,
+    pub symmetry: SpeciesSymmetry,
+    pub orientations: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SpeciesLoadFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SpeciesLoadReport {
+    pub loaded: Vec<SpeciesLoadEntry>,
+    pub failures: Vec<SpeciesLoadFailure>,
+}
+
+fn process_lif(text: &str) -> Result<String, String> {
+    const PATTERN_COMMENT: &str = r"^#.*$";
+    const PATTERN_SIZE: &str = r"^\s*x\s*=\s*(?P<X>\d*)\s*,\s*y\s*=\s*(?P<Y>\d*).*$";
+    const PATTERN_RLE: &str = r"^(\d*[bo\$!])*$";
+    const PATTERN_RLE2: &str = r"((?P<rle1>\d*)(?P<rle2>[bo\$!]))";
 
     let mut buffer = String::new();
 
-    match RegexSetBuilder::new(&[PATTERN_COMMENT, PATTERN_SIZE, PATTERN_RLE])
+    // Run counts are checked before expanding them, no pattern may outgrow the world
+    let mut width: usize = 0;
+    let mut height: usize = 1;
+
+    let regex_rle = match RegexBuilder::new(PATTERN_RLE2).build() {
+        Err(_) => unreachable!(),
+        Ok(regex_rle) => regex_rle,
+    };
+
+    let set = match RegexSetBuilder::new([PATTERN_COMMENT, PATTERN_SIZE, PATTERN_RLE])
         .multi_line(true)
         .build()
     {
         Err(_) => unreachable!(),
-        Ok(set) => {
-            text.lines()
-                .filter(|line| set.is_match(line))
-                .for_each(|line| {
-                    for i in set.matches(line) {
-                        match i {
-                            0 => {
-                                // println!("Comentario : {}", line)
-                            }
-                            1 => {
-                                // println!("Tamanio : {}", line);
-
-                                // match RegexBuilder::new(PATTERN_SIZE).build() {
-                                //     Err(_) => unreachable!(),
-                                //     Ok(regex_rle) => {
-                                //         for c in regex_rle.captures_iter(line) {
-                                //             let xxx = match (&c["X"]).parse::<u16>() {
-                                //                 Err(_) => unreachable!(),
-                                //                 Ok(vx) => vx,
-                                //             };
-                                //             let yyy = match (&c["Y"]).parse::<u16>() {
-                                //                 Err(_) => unreachable!(),
-                                //                 Ok(vy) => vy,
-                                //             };
-                                //             // println!("X {} Y {}", xxx, yyy);
-                                //         }
-                                //     }
-                                // }
-                            }
-                            2 => {
-                                // println!("Contenido : {}", line);
-
-                                match RegexBuilder::new(PATTERN_RLE2).build() {
-                                    Err(_) => unreachable!(),
-                                    Ok(regex_rle) => {
-                                        for c in regex_rle.captures_iter(line) {
-                                            let nseg = match (&c["rle1"]).parse::<u16>() {
-                                                Err(_) => 1,
-                                                Ok(vseg) => vseg,
-                                            };
-                                            let cseg = match &c["rle2"] {
-                                                "b" => " ",
-                                                "o" => "X",
-                                                "$" => "\n",
-                                                "!" => return,
-                                                _ => unreachable!(),
-                                            };
-                                            buffer.push_str(
-                                                ((0..nseg).map(|_| cseg).collect::<String>())
-                                                    .as_str(),
-                                            );
-                                        }
-                                    }
+        Ok(set) => set,
+    };
+
+    'lines: for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let matches = set.matches(line);
+
+        if !matches.matched_any() {
+            return Err(format!("Unrecognized line {}: {}", line_number + 1, line));
+        }
+
+        for i in matches {
+            match i {
+                0 | 1 => {
+                    // Comments and the size header carry no cells
+                }
+                2 => {
+                    for c in regex_rle.captures_iter(line) {
+                        let nseg: usize = match &c["rle1"] {
+                            "" => 1,
+                            count => match count.parse::<usize>() {
+                                Ok(n) if n <= WORLD_SIZE_X.max(WORLD_SIZE_Y) as usize => n,
+                                _ => {
+                                    return Err(format!(
+                                        "Run count {} on line {} is larger than the world",
+                                        count,
+                                        line_number + 1
+                                    ));
                                 }
-                            }
+                            },
+                        };
+                        let cseg = match &c["rle2"] {
+                            "b" => ".",
+                            "o" => "X",
+                            "$" => "\n",
+                            "!" => break 'lines,
                             _ => unreachable!(),
+                        };
+                        match cseg {
+                            "\n" => {
+                                width = 0;
+                                height += nseg;
+                            },
+                            _ => width += nseg,
                         }
+                        if width > WORLD_SIZE_X as usize || height > WORLD_SIZE_Y as usize {
+                            return Err(format!(
+                                "Pattern is larger than the {}x{} world",
+                                WORLD_SIZE_X, WORLD_SIZE_Y
+                            ));
+                        }
+                        buffer.push_str(((0..nseg).map(|_| cseg).collect::<String>()).as_str());
                     }
-                });
+                }
+                _ => unreachable!(),
+            }
         }
     }
 
-    buffer
+    Ok(buffer)
 }
 
-pub fn load_plaintext_species(universe: &mut Universe) -> () {
-    match glob("**/*.cells") {
-        Err(e) => {
-            eprintln!("{:?}", e);
-        },
-        Ok(paths) => {
-            for p in paths {
-                match p {
-                    Err(e) => {
-                        eprintln!("{:?}", e);
-                    },
-                    Ok(path) => {
-                        println!("Loading species: {}", path.display());
-
-                        match read_to_string(path) {
+pub fn load_species_library(paths: &[PathBuf]) -> (Vec<Species>, SpeciesLoadReport) {
+    let mut species = Vec::new();
+    let mut report = SpeciesLoadReport::default();
+
+    let mut push_failure = |path: &Path, error: String| {
+        report.failures.push(SpeciesLoadFailure {
+            path: path.display().to_string(),
+            error,
+        });
+    };
+
+    let mut files: Vec<PathBuf> = Vec::new();
+
+    for library_path in paths {
+        if library_path.is_file() {
+            files.push(library_path.clone());
+        } else if library_path.is_dir() {
+            let pattern = format!("{}/**/*", Pattern::escape(&library_path.to_string_lossy()));
+            match glob(pattern.as_str()) {
+                Err(e) => {
+                    push_failure(library_path, e.to_string());
+                },
+                Ok(entries) => {
+                    for entry in entries {
+                        match entry {
                             Err(e) => {
-                                eprintln!("{:?}", e);
+                                push_failure(e.path(), e.error().to_string());
                             },
-                            Ok(content) => {
-                                let entity_base = species_plaintext_to_vec(content.as_str());
-
-                                let entities = species_from_base(&entity_base);
-
-                                for entity in entities {
-                                    universe.push_entity(entity);
+                            Ok(path) => {
+                                if path.is_file() && species_format(&path).is_some() {
+                                    files.push(path);
                                 }
                             }
                         }
                     }
                 }
             }
+        } else {
+            push_failure(library_path, "Path not found".to_string());
+        }
+    }
+
+    for path in files {
+        match load_species_file(&path) {
+            Err(e) => {
+                push_failure(&path, e);
+            },
+            Ok(s) => {
+                report.loaded.push(SpeciesLoadEntry {
+                    path: path.display().to_string(),
+                    name: s.name.clone(),
+                    symmetry: s.symmetry,
+                    orientations: s.orientations.len(),
+                });
+                species.push(s);
+            }
         }
     }
+
+    println!(
+        "Loaded species library: {} loaded, {} failed",
+        report.loaded.len(),
+        report.failures.len()
+    );
+
+    (species, report)
+}
+
+fn species_format(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("cells") => Some("cells"),
+        Some("lif") | Some("rle") => Some("rle"),
+        _ => None,
+    }
 }
 
-pub fn load_rle_species(universe: &mut Universe) -> () {
-    match glob("**/*.lif") {
-        Err(e) => {
-            eprintln!("{:?}", e);
-        },
-        Ok(paths) => {
-            for p in paths {
-                match p {
-                    Err(e) => {
-                        eprintln!("{:?}", e);
-                    },
-                    Ok(path) => {
-                        println!("Loading species: {}", path.display());
-
-                        match read_to_string(path) {
-                            Err(e) => {
-                                eprintln!("{:?}", e);
-                            },
-                            Ok(content) => {
-                                let raw_content = process_lif(content.as_str());
+pub fn load_species_file(path: &Path) -> Result<Species, String> {
+    let content = read_to_string(path).map_err(|e| e.to_string())?;
 
-                                let entity_base = species_plaintext_to_vec(raw_content.as_str());
+    let entity_base = match species_format(path) {
+        Some("cells") => species_plaintext_to_vec(content.as_str())?,
+        Some("rle") => species_rle_to_vec(content.as_str())?,
+        _ => return Err("Unknown pattern format".to_string()),
+    };
 
-                                let entities = species_from_base(&entity_base);
+    let name = match path.file_stem() {
+        None => path.display().to_string(),
+        Some(stem) => stem.to_string_lossy().to_string(),
+    };
 
-                                for entity in entities {
-                                    universe.push_entity(entity);
-                                }
-                            }
-                        }
-                    }
-                }
+    let mut species =
+        species_from_base(name.as_str(), &entity_base).ok_or_else(|| "Empty pattern".to_string())?;
+
+    parse_species_metadata(content.as_str(), &mut species.metadata);
+
+    classify_species(&mut species);
+
+    Ok(species)
+}
+
+// Fill the metadata carried in RLE (#N, #O, #C, header) or plaintext (!Name:, !Author:) comments
+fn parse_species_metadata(text: &str, metadata: &mut SpeciesMetadata) {
+    const PATTERN_RULE: &str = r"rule\s*=\s*(?P<rule>[^\s,]+)";
+    const PATTERN_PERIOD: &str = r"(?i)\bperiod\s*[:=]?\s*(?P<period>\d+)|\bp(?P<p>\d+)\b";
+    const PATTERN_VELOCITY: &str = r"(?P<velocity>\d*c/\d+(\s*(orthogonal|diagonal))?)";
+
+    let (regex_rule, regex_period, regex_velocity) = match (
+        Regex::new(PATTERN_RULE),
+        Regex::new(PATTERN_PERIOD),
+        Regex::new(PATTERN_VELOCITY),
+    ) {
+        (Ok(rule), Ok(period), Ok(velocity)) => (rule, period, velocity),
+        _ => unreachable!(),
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        let comment = if let Some(rest) = line.strip_prefix("#N") {
+            metadata.title = Some(rest.trim().to_string());
+            continue;
+        } else if let Some(rest) = line.strip_prefix("#O") {
+            metadata.author = Some(rest.trim().to_string());
+            continue;
+        } else if let Some(rest) = line.strip_prefix("!Name:") {
+            metadata.title = Some(rest.trim().to_string());
+            continue;
+        } else if let Some(rest) = line.strip_prefix("!Author:") {
+            metadata.author = Some(rest.trim().to_string());
+            continue;
+        } else if let Some(rest) = line.strip_prefix("#C").or_else(|| line.strip_prefix("#c")) {
+            rest.trim()
+        } else if let Some(rest) = line.strip_prefix('!') {
+            rest.trim()
+        } else {
+            if let Some(c) = regex_rule.captures(line) {
+                metadata.rule = c["rule"].to_string();
+            }
+            continue;
+        };
+
+        if comment.is_empty() {
+            continue;
+        }
+
+        if let Some(tags) = comment
+            .strip_prefix("Tags:")
+            .or_else(|| comment.strip_prefix("tags:"))
+        {
+            metadata.tags.extend(
+                tags.split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty()),
+            );
+            continue;
+        }
+
+        if metadata.period.is_none() {
+            if let Some(c) = regex_period.captures(comment) {
+                metadata.period = c
+                    .name("period")
+                    .or_else(|| c.name("p"))
+                    .and_then(|m| m.as_str().parse::<usize>().ok());
             }
         }
+
+        if metadata.velocity.is_none() {
+            if let Some(c) = regex_velocity.captures(comment) {
+                metadata.velocity = Some(c["velocity"].to_string());
+            }
+        }
+
+        metadata.comments.push(comment.to_string());
     }
 }
 
-fn species_from_base(entity: &UniversePlane) -> UniversePlaneSet {
-    let mut entities = Vec::new();
+fn classify_species(species: &mut Species) {
+    // Classification runs the engine rule, so foreign rules are left as declared
+    if normalize_rule(&species.metadata.rule) != DEFAULT_RULE {
+        return;
+    }
 
-    entities.push(species_nop(&entity));
+    let report = classify_pattern(species.get_base());
+    let metadata = &mut species.metadata;
 
-    entities.push(species_rotate_90(&entity));
+    if metadata.period.is_none() {
+        metadata.period = report.class.get_period();
+    }
+    if metadata.velocity.is_none() {
+        metadata.velocity = report.class.get_velocity();
+    }
+    let tag = report.class.get_tag();
+    if !metadata.tags.iter().any(|t| t == tag) {
+        metadata.tags.push(tag.to_string());
+    }
 
-    let entity1 = species_rotate_90(&entity);
-    entities.push(species_rotate_90(&entity1));
+    metadata.classification = Some(report);
+}
 
-    let entity1 = species_rotate_90(&entity);
-    let entity1 = species_rotate_90(&entity1);
-    entities.push(species_rotate_90(&entity1));
+// Accept both B3/S23 and the older 23/3 survival/birth notation
+pub fn normalize_rule(rule: &str) -> String {
+    let rule: String = rule
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
 
-    entities.push(species_flip_h(&entity));
+    if rule.contains('B') {
+        return rule;
+    }
 
-    entities.push(species_flip_v(&entity));
+    match rule.split_once('/') {
+        None => rule,
+        Some((survival, birth)) => format!("B{}/S{}", birth, survival),
+    }
+}
+
+pub fn species_rle_to_vec(text: &str) -> Result<UniversePlane, String> {
+    let raw_content = process_lif(text)?;
 
-    entities
+    species_plaintext_to_vec(raw_content.as_str())
 }
 
-fn species_plaintext_to_vec(text: &str) -> UniversePlane {
+pub fn species_transform(entity: &UniversePlane, transform: SpeciesTransform) -> UniversePlane {
+    match transform {
+        SpeciesTransform::Nop => species_nop(entity),
+        SpeciesTransform::Rotate90 => species_rotate_90(entity),
+        SpeciesTransform::Rotate180 => species_rotate_90(&species_rotate_90(entity)),
+        SpeciesTransform::Rotate270 => {
+            species_rotate_90(&species_rotate_90(&species_rotate_90(entity)))
+        }
+        SpeciesTransform::FlipH => species_flip_h(entity),
+        SpeciesTransform::FlipV => species_flip_v(entity),
+        SpeciesTransform::FlipDiagonal => species_flip_diagonal(entity),
+        SpeciesTransform::FlipAntiDiagonal => {
+            species_flip_diagonal(&species_rotate_90(&species_rotate_90(entity)))
+        }
+    }
+}
+
+pub fn species_from_base(name: &str, entity: &UniversePlane) -> Option<Species> {
+    let entity = species_trim(entity)?;
+
+    let mut orientations: Vec<(SpeciesTransform, UniversePlane)> = Vec::new();
+    let mut invariants: Vec<SpeciesTransform> = Vec::new();
+
+    for transform in SPECIES_TRANSFORMS {
+        let entity_transformed = species_transform(&entity, transform);
+
+        if entity_transformed == entity {
+            invariants.push(transform);
+        }
+
+        if !orientations.iter().any(|(_, e)| *e == entity_transformed) {
+            orientations.push((transform, entity_transformed));
+        }
+    }
+
+    let metadata = SpeciesMetadata {
+        width: entity.len(),
+        height: entity[0].len(),
+        population: entity.iter().flatten().filter(|c| **c == CELL_LIVE).count(),
+        ..SpeciesMetadata::default()
+    };
+
+    Some(Species {
+        name: name.to_string(),
+        symmetry: species_symmetry(&invariants),
+        metadata,
+        orientations,
+    })
+}
+
+fn species_symmetry(invariants: &[SpeciesTransform]) -> SpeciesSymmetry {
+    let has = |transform: SpeciesTransform| invariants.contains(&transform);
+
+    if has(SpeciesTransform::Rotate90) {
+        match has(SpeciesTransform::FlipH) {
+            true => SpeciesSymmetry::D4,
+            false => SpeciesSymmetry::C4,
+        }
+    } else if has(SpeciesTransform::Rotate180) {
+        if has(SpeciesTransform::FlipH) {
+            SpeciesSymmetry::D2Orthogonal
+        } else if has(SpeciesTransform::FlipDiagonal) {
+            SpeciesSymmetry::D2Diagonal
+        } else {
+            SpeciesSymmetry::C2
+        }
+    } else if has(SpeciesTransform::FlipH) || has(SpeciesTransform::FlipV) {
+        SpeciesSymmetry::D1Orthogonal
+    } else if has(SpeciesTransform::FlipDiagonal) || has(SpeciesTransform::FlipAntiDiagonal) {
+        SpeciesSymmetry::D1Diagonal
+    } else {
+        SpeciesSymmetry::C1
+    }
+}
+
+// Crop the dead margin so orientations compare by shape only
+pub fn species_trim(entity_source: &UniversePlane) -> Option<UniversePlane> {
+    let mut min_a = usize::MAX;
+    let mut max_a = 0;
+    let mut min_b = usize::MAX;
+    let mut max_b = 0;
+
+    for (a, column) in entity_source.iter().enumerate() {
+        for (b, cell) in column.iter().enumerate() {
+            if *cell == CELL_LIVE {
+                min_a = min_a.min(a);
+                max_a = max_a.max(a);
+                min_b = min_b.min(b);
+                max_b = max_b.max(b);
+            }
+        }
+    }
+
+    if min_a == usize::MAX {
+        return None;
+    }
+
+    let mut entity: UniversePlane = vec![vec![CELL_DEATH; max_b - min_b + 1]; max_a - min_a + 1];
+
+    for a in min_a..=max_a {
+        for b in min_b..=max_b {
+            entity[a - min_a][b - min_b] = entity_source[a][b];
+        }
+    }
+
+    Some(entity)
+}
+
+fn species_plaintext_to_vec(text: &str) -> Result<UniversePlane, String> {
     let mut max_x: usize = 0;
     let mut max_y: usize = 0;
 
@@ -189,7 +593,14 @@ fn species_plaintext_to_vec(text: &str) -> UniversePlane {
         max_y += 1;
     }
 
-    let mut entity: UniversePlane = vec![vec![CELL_DEATH; max_y as usize]; max_x as usize];
+    if max_x > WORLD_SIZE_X as usize || max_y > WORLD_SIZE_Y as usize {
+        return Err(format!(
+            "Pattern is larger than the {}x{} world",
+            WORLD_SIZE_X, WORLD_SIZE_Y
+        ));
+    }
+
+    let mut entity: UniversePlane = vec![vec![CELL_DEATH; max_y]; max_x];
 
     let mut char_y: usize = 0;
 
@@ -197,18 +608,19 @@ fn species_plaintext_to_vec(text: &str) -> UniversePlane {
         if line.trim().starts_with('!') {
             continue;
         }
-        let mut char_x: usize = 0;
-        for c in line.trim().chars() {
+        for (char_x, c) in line.trim().chars().enumerate() {
             entity[char_x][char_y] = match c {
                 '.' => CELL_DEATH,
-                _ => CELL_LIVE,
+                'O' | 'X' | '*' => CELL_LIVE,
+                _ => {
+                    return Err(format!("Invalid cell '{}' at line {}", c, char_y + 1));
+                }
             };
-            char_x += 1;
         }
         char_y += 1;
     }
 
-    entity
+    Ok(entity)
 }
 
 fn species_rotate_90(entity_source: &UniversePlane) -> UniversePlane {
@@ -216,7 +628,7 @@ fn species_rotate_90(entity_source: &UniversePlane) -> UniversePlane {
     let source_max_y: usize = entity_source[0].len();
 
     let mut entity: UniversePlane =
-        vec![vec![CELL_DEATH; source_max_x as usize]; source_max_y as usize];
+        vec![vec![CELL_DEATH; source_max_x]; source_max_y];
 
     for a in 0..source_max_x {
         for b in 0..source_max_y {
@@ -232,7 +644,7 @@ fn species_flip_h(entity_source: &UniversePlane) -> UniversePlane {
     let source_max_y: usize = entity_source[0].len();
 
     let mut entity: UniversePlane =
-        vec![vec![CELL_DEATH; source_max_y as usize]; source_max_x as usize];
+        vec![vec![CELL_DEATH; source_max_y]; source_max_x];
 
     for a in 0..source_max_x {
         for b in 0..source_max_y {
@@ -248,7 +660,7 @@ fn species_flip_v(entity_source: &UniversePlane) -> UniversePlane {
     let source_max_y: usize = entity_source[0].len();
 
     let mut entity: UniversePlane =
-        vec![vec![CELL_DEATH; source_max_y as usize]; source_max_x as usize];
+        vec![vec![CELL_DEATH; source_max_y]; source_max_x];
 
     for a in 0..source_max_x {
         for b in 0..source_max_y {
@@ -259,12 +671,27 @@ fn species_flip_v(entity_source: &UniversePlane) -> UniversePlane {
     entity
 }
 
+fn species_flip_diagonal(entity_source: &UniversePlane) -> UniversePlane {
+    let source_max_x: usize = entity_source.len();
+    let source_max_y: usize = entity_source[0].len();
+
+    let mut entity: UniversePlane = vec![vec![CELL_DEATH; source_max_x]; source_max_y];
+
+    for a in 0..source_max_x {
+        for b in 0..source_max_y {
+            entity[b][a] = entity_source[a][b];
+        }
+    }
+
+    entity
+}
+
 pub fn species_nop(entity_source: &UniversePlane) -> UniversePlane {
     let source_max_x: usize = entity_source.len();
     let source_max_y: usize = entity_source[0].len();
 
     let mut entity: UniversePlane =
-        vec![vec![CELL_DEATH; source_max_y as usize]; source_max_x as usize];
+        vec![vec![CELL_DEATH; source_max_y]; source_max_x];
 
     for a in 0..source_max_x {
         for b in 0..source_max_y {
@@ -274,3 +701,35 @@ pub fn species_nop(entity_source: &UniversePlane) -> UniversePlane {
 
     entity
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glider_has_eight_orientations() {
+        let glider = species_rle_to_vec("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+
+        let species = species_from_base("glider", &glider).unwrap();
+
+        assert_eq!(species.orientations.len(), 8);
+        assert_eq!(species.orientations[0].0, SpeciesTransform::Nop);
+        assert_eq!(species.symmetry, SpeciesSymmetry::C1);
+    }
+
+    #[test]
+    fn block_has_one_orientation() {
+        let block = species_rle_to_vec("x = 2, y = 2\n2o$2o!").unwrap();
+
+        let species = species_from_base("block", &block).unwrap();
+
+        assert_eq!(species.orientations.len(), 1);
+        assert_eq!(species.symmetry, SpeciesSymmetry::D4);
+    }
+
+    #[test]
+    fn run_counts_larger_than_the_world_are_rejected() {
+        assert!(species_rle_to_vec("x = 1, y = 1\n99999999999999999999999o!").is_err());
+        assert!(species_rle_to_vec("x = 1, y = 1\n1025o!").is_err());
+    }
+}
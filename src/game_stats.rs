@@ -0,0 +1,123 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::game_constants::{MAX_X, MAX_Y, MIN_X, MIN_Y};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActivityBounds {
+    pub min_x: isize,
+    pub min_y: isize,
+    pub max_x: isize,
+    pub max_y: isize,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct GenerationStats {
+    pub generation: usize,
+    pub population: usize,
+    pub births: usize,
+    pub deaths: usize,
+    pub changed: usize,
+    // Bounding box of the cells born or died in this step
+    pub activity: Option<ActivityBounds>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StatsQuery {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub from: Option<usize>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub to: Option<usize>,
+    #[serde(default)]
+    pub format: StatsFormat,
+}
+
+// Query strings like from=&to= leave the value empty, which means unset
+pub fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    match Option::<String>::deserialize(deserializer)?.as_deref() {
+        None | Some("") => Ok(None),
+        Some(text) => text.parse::<T>().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+impl GenerationStats {
+    pub fn new(generation: usize) -> GenerationStats {
+        GenerationStats {
+            generation,
+            population: 0,
+            births: 0,
+            deaths: 0,
+            changed: 0,
+            activity: None,
+        }
+    }
+
+    #[inline]
+    pub fn count_cell(&mut self, x_pos: isize, y_pos: isize, alive: bool, changed: bool) {
+        if alive {
+            self.population += 1;
+        }
+        if !changed {
+            return;
+        }
+
+        self.changed += 1;
+        match alive {
+            true => self.births += 1,
+            false => self.deaths += 1,
+        }
+
+        let activity = self.activity.get_or_insert(ActivityBounds {
+            min_x: MAX_X,
+            min_y: MAX_Y,
+            max_x: MIN_X,
+            max_y: MIN_Y,
+        });
+        activity.min_x = activity.min_x.min(x_pos);
+        activity.min_y = activity.min_y.min(y_pos);
+        activity.max_x = activity.max_x.max(x_pos);
+        activity.max_y = activity.max_y.max(y_pos);
+    }
+}
+
+impl StatsQuery {
+    #[inline]
+    pub fn matches(&self, stats: &GenerationStats) -> bool {
+        self.from.is_none_or(|from| stats.generation >= from)
+            && self.to.is_none_or(|to| stats.generation <= to)
+    }
+}
+
+pub fn stats_to_csv<'a>(stats: impl Iterator<Item = &'a GenerationStats>) -> String {
+    let mut csv = String::from("generation,population,births,deaths,changed,min_x,min_y,max_x,max_y\n");
+
+    for s in stats {
+        let activity = match s.activity {
+            None => ",,,".to_string(),
+            Some(a) => format!("{},{},{},{}", a.min_x, a.min_y, a.max_x, a.max_y),
+        };
+        csv.push_str(
+            format!(
+                "{},{},{},{},{},{}\n",
+                s.generation, s.population, s.births, s.deaths, s.changed, activity
+            )
+            .as_str(),
+        );
+    }
+
+    csv
+}
@@ -0,0 +1,198 @@
+use actix_web::web::Bytes;
+use actix_ws::{Message, MessageStream, Session};
+use futures::Stream;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::game_constants::{
+    UniversePlane, CELL_LIVE, STREAM_FRAME_DELTA,
+};
+use crate::game_engine::EngineControl;
+use crate::game_entropy::InjectionReport;
+use crate::game_region::region_binary;
+use crate::game_universe::{BoundsLimits, Universe, WorldBounds};
+
+// What the engine thread broadcasts to WebSocket and SSE clients
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineEvent {
+    Generation {
+        generation: usize,
+        population: usize,
+        elapsed_ms: i64,
+    },
+    Injection(InjectionReport),
+    Control(EngineControl),
+}
+
+impl EngineEvent {
+    fn get_name(&self) -> &'static str {
+        match self {
+            EngineEvent::Generation { .. } => "generation",
+            EngineEvent::Injection(_) => "injection",
+            EngineEvent::Control(_) => "control",
+        }
+    }
+}
+
+// Frames are little endian
+//
+// keyframe: u8 kind = 1, u64 generation, i32 x, i32 y, u16 w, u16 h,
+//           then w * h bits row by row, least significant bit first, the /region binary body
+// delta:    u8 kind = 2, u64 generation, u32 born, u32 died,
+//           then born and died cells as u16 x, u16 y pairs relative to the viewport
+//
+// The client subscribes, and moves its viewport, by sending {"x":..,"y":..,"w":..,"h":..} as text
+
+pub async fn viewport_session(
+    mut session: Session,
+    mut messages: MessageStream,
+    rwlock: Arc<RwLock<Universe>>,
+    limits: BoundsLimits,
+    mut events: Receiver<EngineEvent>,
+) {
+    let mut viewport: Option<(WorldBounds, UniversePlane)> = None;
+
+    loop {
+        let frame = tokio::select! {
+            message = messages.recv() => match message {
+                None | Some(Err(_)) => break,
+                Some(Ok(Message::Text(text))) => match parse_viewport(&text, &limits) {
+                    Err(e) => {
+                        if session.text(e).await.is_err() {
+                            break;
+                        }
+                        None
+                    },
+                    Ok(bounds) => match snapshot_viewport(&rwlock, bounds) {
+                        None => break,
+                        Some((generation, world)) => {
+                            let frame = region_binary(generation, &bounds, &world);
+                            viewport = Some((bounds, world));
+                            Some(frame)
+                        }
+                    }
+                },
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                    None
+                },
+                Some(Ok(Message::Close(reason))) => {
+                    let _ = session.close(reason).await;
+                    return;
+                },
+                Some(Ok(_)) => None,
+            },
+            event = events.recv() => match (event, &viewport) {
+                (Err(RecvError::Closed), _) => break,
+                (_, None) => None,
+                (Ok(EngineEvent::Injection(_)), _) | (Ok(EngineEvent::Control(_)), _) => None,
+                // A lagging client skips generations, the delta is against what it was last sent
+                (_, Some((bounds, last_world))) => match snapshot_viewport(&rwlock, *bounds) {
+                    None => break,
+                    Some((generation, world)) => {
+                        let frame = encode_delta(generation, bounds, last_world, &world);
+                        viewport = Some((*bounds, world));
+                        Some(frame)
+                    }
+                }
+            },
+        };
+
+        if let Some(frame) = frame {
+            if session.binary(frame).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    let _ = session.close(None).await;
+}
+
+fn parse_viewport(text: &str, limits: &BoundsLimits) -> Result<WorldBounds, String> {
+    let bounds = serde_json::from_str::<WorldBounds>(text).map_err(|e| e.to_string())?;
+
+    bounds.validate(limits)?;
+
+    Ok(bounds)
+}
+
+fn snapshot_viewport(
+    rwlock: &Arc<RwLock<Universe>>,
+    bounds: WorldBounds,
+) -> Option<(usize, UniversePlane)> {
+    match rwlock.read() {
+        Err(e) => {
+            eprintln!("{:?}", e);
+            None
+        },
+        Ok(universe) => Some((universe.get_generation(), universe.get_current_world(bounds))),
+    }
+}
+
+// Falls back to a keyframe when the changes would take more room than the whole viewport
+fn encode_delta(
+    generation: usize,
+    bounds: &WorldBounds,
+    last_world: &UniversePlane,
+    world: &UniversePlane,
+) -> Vec<u8> {
+    let mut born: Vec<(u16, u16)> = Vec::new();
+    let mut died: Vec<(u16, u16)> = Vec::new();
+
+    for x_index in 0..bounds.w as usize {
+        for y_index in 0..bounds.h as usize {
+            let (old_state, new_state) = (last_world[x_index][y_index], world[x_index][y_index]);
+            if old_state != new_state {
+                match new_state {
+                    CELL_LIVE => born.push((x_index as u16, y_index as u16)),
+                    _ => died.push((x_index as u16, y_index as u16)),
+                }
+            }
+        }
+    }
+
+    if (born.len() + died.len()) * 4 > ((bounds.w * bounds.h) as usize).div_ceil(8) {
+        return region_binary(generation, bounds, world);
+    }
+
+    let mut frame: Vec<u8> = Vec::with_capacity(17 + (born.len() + died.len()) * 4);
+    frame.push(STREAM_FRAME_DELTA);
+    frame.extend_from_slice(&(generation as u64).to_le_bytes());
+    frame.extend_from_slice(&(born.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&(died.len() as u32).to_le_bytes());
+    for (x_index, y_index) in born.iter().chain(died.iter()) {
+        frame.extend_from_slice(&x_index.to_le_bytes());
+        frame.extend_from_slice(&y_index.to_le_bytes());
+    }
+
+    frame
+}
+
+// Server-Sent Events, named after the event type with its JSON as data
+pub fn event_stream(
+    events: Receiver<EngineEvent>,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    futures::stream::unfold(events, |mut events| async move {
+        loop {
+            match events.recv().await {
+                Err(RecvError::Closed) => return None,
+                // A slow client misses events rather than holding the engine back
+                Err(RecvError::Lagged(_)) => continue,
+                Ok(event) => match serde_json::to_string(&event) {
+                    Err(e) => {
+                        eprintln!("{:?}", e);
+                        continue;
+                    },
+                    Ok(data) => {
+                        let frame = format!("event: {}\ndata: {}\n\n", event.get_name(), data);
+                        return Some((Ok(Bytes::from(frame)), events));
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use std::hash::{Hash, Hasher};
+
+use crate::game_constants::{
+    MIN_X, MIN_Y, TILE_MAX_ZOOM, TILE_SIZE, WORLD_SIZE_X, WORLD_SIZE_Y,
+};
+use crate::game_render::{RenderOptions, RenderQuery};
+use crate::game_universe::WorldBounds;
+
+// Zoom 0 is the whole world in one tile, every level splits each tile in four
+#[derive(Deserialize, Debug)]
+pub struct TilePath {
+    pub z: u32,
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub bounds: WorldBounds,
+    pub scale: Option<usize>,
+    pub zoom_out: Option<usize>,
+}
+
+impl TilePath {
+    // Tile coordinates wrap around the torus, the cell size follows the zoom level
+    pub fn get_tile(&self) -> Result<Tile, String> {
+        if self.z > TILE_MAX_ZOOM {
+            return Err(format!("Zoom levels go from 0 to {}", TILE_MAX_ZOOM));
+        }
+
+        let tiles = 1isize << self.z;
+        let (w, h) = (WORLD_SIZE_X / tiles, WORLD_SIZE_Y / tiles);
+
+        let bounds = WorldBounds {
+            x: MIN_X + self.x.rem_euclid(tiles) * w,
+            y: MIN_Y + self.y.rem_euclid(tiles) * h,
+            w,
+            h,
+        };
+
+        let cells = w.max(h) as usize;
+
+        Ok(match cells >= TILE_SIZE {
+            true => Tile { bounds, scale: None, zoom_out: Some(cells / TILE_SIZE) },
+            false => Tile { bounds, scale: Some(TILE_SIZE / cells), zoom_out: None },
+        })
+    }
+}
+
+impl Tile {
+    // Colors and grid come from the query, the pixel size is the tile's own
+    pub fn get_render(&self, query: RenderQuery) -> Result<RenderQuery, String> {
+        if query.scale.is_some() || query.zoom_out.is_some() {
+            return Err("Tiles pick scale and zoom_out from the zoom level".to_string());
+        }
+
+        Ok(RenderQuery {
+            scale: self.scale,
+            zoom_out: self.zoom_out,
+            ..query
+        })
+    }
+}
+
+// FNV-1a, unlike DefaultHasher its output does not change between builds or restarts
+struct TileHasher(u64);
+
+impl Hasher for TileHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Same cells with the same render options give the same tag, whatever the generation,
+// so a still tile is not sent again every step; X-Generation tells which one it shows
+pub fn tile_etag(options: &RenderOptions, values: &[Vec<f32>]) -> String {
+    let mut hasher = TileHasher(0xcbf2_9ce4_8422_2325);
+
+    options.hash(&mut hasher);
+    for column in values {
+        for value in column {
+            value.to_bits().hash(&mut hasher);
+        }
+    }
+
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_constants::{CELL_DEATH, CELL_LIVE};
+    use crate::game_render::cell_values;
+
+    fn tile_values(live: bool) -> Vec<Vec<f32>> {
+        let mut world = vec![vec![CELL_DEATH; TILE_SIZE]; TILE_SIZE];
+        world[10][20] = if live { CELL_LIVE } else { CELL_DEATH };
+        cell_values(&world)
+    }
+
+    #[test]
+    fn same_tile_content_has_the_same_etag() {
+        let options = RenderQuery::default().get_options(TILE_SIZE as isize, TILE_SIZE as isize).unwrap();
+
+        assert_eq!(tile_etag(&options, &tile_values(true)), tile_etag(&options, &tile_values(true)));
+        assert_ne!(tile_etag(&options, &tile_values(true)), tile_etag(&options, &tile_values(false)));
+    }
+}
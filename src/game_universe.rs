@@ -1,10 +1,19 @@
+use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
+use crate::game_engine::EngineControl;
+use crate::game_entropy::{EntropyPolicy, InjectionReport};
+use crate::game_period::{zobrist_key, CycleDetector, WorldCycle};
+use crate::game_seeding::WorldSeeding;
+use crate::game_species::{Species, SpeciesLoadReport};
+use crate::game_stats::GenerationStats;
 use crate::game_constants::{
-    CELL_DEATH, HISTORY_SIZE, MAX_X, MAX_Y, MIN_X, MIN_Y, WORLD_SIZE_X, WORLD_SIZE_Y, UniversePlane, UniversePlaneSet, UniverseCell
+    CELL_DEATH, CELL_LIVE, HISTORY_SIZE, MAX_X, MAX_Y, MIN_X, MIN_Y, WORLD_SIZE_X, WORLD_SIZE_Y,
+    ENTROPY_EVENTS_SIZE, STATS_HISTORY_SIZE, UniversePlane, UniverseCell,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WorldBounds {
     pub x: isize,
     pub y: isize,
@@ -12,38 +21,168 @@ pub struct WorldBounds {
     pub h: isize,
 }
 
+impl WorldBounds {
+    pub fn validate(&self, limits: &BoundsLimits) -> Result<(), String> {
+        if self.w <= 0 || self.h <= 0 {
+            return Err(format!("Region size must be positive, got {}x{}", self.w, self.h));
+        }
+        if self.w > limits.max_w || self.h > limits.max_h {
+            return Err(format!(
+                "Region {}x{} is larger than the {}x{} limit",
+                self.w, self.h, limits.max_w, limits.max_h
+            ));
+        }
+        check_position(self.x, self.y)
+    }
+}
+
+// Positions wrap around the torus, one world of slack covers viewports centered near the edges
+pub fn check_position(x: isize, y: isize) -> Result<(), String> {
+    if !(MIN_X - WORLD_SIZE_X..=MAX_X + WORLD_SIZE_X).contains(&x) || !(MIN_Y - WORLD_SIZE_Y..=MAX_Y + WORLD_SIZE_Y).contains(&y) {
+        return Err(format!("Position {},{} is more than one world away", x, y));
+    }
+
+    Ok(())
+}
+
+// Largest region a single request may read
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct BoundsLimits {
+    pub max_w: isize,
+    pub max_h: isize,
+}
+
+impl Default for BoundsLimits {
+    fn default() -> BoundsLimits {
+        BoundsLimits {
+            max_w: WORLD_SIZE_X,
+            max_h: WORLD_SIZE_Y,
+        }
+    }
+}
+
+impl BoundsLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_w <= 0 || self.max_h <= 0 || self.max_w > WORLD_SIZE_X || self.max_h > WORLD_SIZE_Y {
+            return Err(format!(
+                "Limits must be between 1x1 and {}x{}",
+                WORLD_SIZE_X, WORLD_SIZE_Y
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementMode {
+    // Copy every cell of the entity, live or dead
+    #[default]
+    Overwrite,
+    // Only add the live cells of the entity
+    Or,
+    // Toggle the cells under the live cells of the entity
+    Xor,
+    // Overwrite and clear a one cell border around the entity
+    Clear,
+}
+
+pub struct Placement {
+    pub x_pos: isize,
+    pub y_pos: isize,
+    pub entity: UniversePlane,
+    pub mode: PlacementMode,
+}
+
 pub struct Universe {
+    seed: u64,
+    rng: StdRng,
     current_time: isize,
+    generation: usize,
     space_time: Vec<UniverseCell>,
-    species: UniversePlaneSet,
+    species: Vec<Species>,
+    species_report: SpeciesLoadReport,
+    pending_placements: Vec<Placement>,
+    stats: VecDeque<GenerationStats>,
+    cycle_detector: CycleDetector,
+    entropy_policy: EntropyPolicy,
+    last_entropy: usize,
+    entropy_events: VecDeque<InjectionReport>,
+    next_event_id: usize,
+    seeding: WorldSeeding,
+    control: EngineControl,
+    // Generations every cell has been alive in a row, None when not tracked
+    ages: Option<Vec<u32>>,
 }
 
 impl Universe {
-    pub fn new() -> Universe {
+    pub fn new(seed: u64) -> Universe {
         let mut universe = Universe {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
             current_time: 0,
+            generation: 0,
             space_time: vec![CELL_DEATH; (WORLD_SIZE_Y * WORLD_SIZE_X * HISTORY_SIZE) as usize],
             species: Vec::new(),
+            species_report: SpeciesLoadReport::default(),
+            pending_placements: Vec::new(),
+            stats: VecDeque::with_capacity(STATS_HISTORY_SIZE),
+            cycle_detector: CycleDetector::new(),
+            entropy_policy: EntropyPolicy::default(),
+            last_entropy: 0,
+            entropy_events: VecDeque::with_capacity(ENTROPY_EVENTS_SIZE),
+            next_event_id: 1,
+            seeding: WorldSeeding::Empty,
+            control: EngineControl::default(),
+            ages: None,
         };
         universe.init_time();
         universe
     }
 
+    // Start over from generation zero with an empty world, keeping the species
+    pub fn reset(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.current_time = 0;
+        self.generation = 0;
+        self.space_time.fill(CELL_DEATH);
+        self.pending_placements.clear();
+        self.stats.clear();
+        self.cycle_detector.clear();
+        self.last_entropy = 0;
+        self.entropy_events.clear();
+        if let Some(ages) = &mut self.ages {
+            ages.fill(0);
+        }
+        self.init_time();
+    }
+
+    #[inline]
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    #[inline]
+    pub fn get_rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
     #[inline]
     pub fn get_dimensions() -> (isize, isize, isize) {
         (HISTORY_SIZE, WORLD_SIZE_X, WORLD_SIZE_Y)
     }
 
-    fn init_time(&mut self) -> () {
+    fn init_time(&mut self) {
         for x_pos in MIN_X..=MAX_X {
             for y_pos in MIN_Y..=MAX_Y {
                 let (x_index, y_index) = self.position_to_index(x_pos, y_pos);
 
+                // Starts empty, game_seeding fills it once the species library is loaded
                 self.space_time[Universe::map_3d_to_1d_index(self.current_time, x_index, y_index)] =
-                    // TODO: Enable this ...
                     CELL_DEATH;
-                // rand::thread_rng().gen_range(CELL_DEATH..=CELL_LIVE);
-                //
             }
         }
     }
@@ -61,12 +200,122 @@ impl Universe {
         x_offset: isize,
         y_offset: isize,
         cell_state: UniverseCell,
-    ) -> () {
+    ) {
         let (x_index, y_index) = self.position_to_index(x_pos + x_offset, y_pos + y_offset);
         let (x_index, y_index) = self.fix_index(x_index, y_index);
         self.space_time[Universe::map_3d_to_1d_index(next_time, x_index, y_index)] = cell_state;
     }
 
+    pub fn get_cell(
+        &self,
+        time: isize,
+        x_pos: isize,
+        y_pos: isize,
+        x_offset: isize,
+        y_offset: isize,
+    ) -> UniverseCell {
+        let (x_index, y_index) = self.position_to_index(x_pos + x_offset, y_pos + y_offset);
+        let (x_index, y_index) = self.fix_index(x_index, y_index);
+        self.space_time[Universe::map_3d_to_1d_index(time, x_index, y_index)]
+    }
+
+    pub fn is_area_free(&self, time: isize, x_pos: isize, y_pos: isize, w: isize, h: isize) -> bool {
+        for i in 0..w.min(WORLD_SIZE_X) {
+            for j in 0..h.min(WORLD_SIZE_Y) {
+                if self.get_cell(time, x_pos, y_pos, i, j) == CELL_LIVE {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn place_entity(
+        &mut self,
+        time: isize,
+        x_pos: isize,
+        y_pos: isize,
+        entity: &UniversePlane,
+        mode: PlacementMode,
+    ) {
+        let entity_xsize = entity.len() as isize;
+        let entity_ysize = entity[0].len() as isize;
+
+        if mode == PlacementMode::Clear {
+            for i in -1..=entity_xsize {
+                self.set_cell(time, x_pos, y_pos, i, -1, CELL_DEATH);
+                self.set_cell(time, x_pos, y_pos, i, entity_ysize, CELL_DEATH);
+            }
+            for j in 0..entity_ysize {
+                self.set_cell(time, x_pos, y_pos, -1, j, CELL_DEATH);
+                self.set_cell(time, x_pos, y_pos, entity_xsize, j, CELL_DEATH);
+            }
+        }
+
+        for i in 0..entity_xsize {
+            for j in 0..entity_ysize {
+                let entity_cell = entity[i as usize][j as usize];
+                let cell_state = match mode {
+                    PlacementMode::Overwrite | PlacementMode::Clear => entity_cell,
+                    PlacementMode::Or => self.get_cell(time, x_pos, y_pos, i, j) | entity_cell,
+                    PlacementMode::Xor => self.get_cell(time, x_pos, y_pos, i, j) ^ entity_cell,
+                };
+                self.set_cell(time, x_pos, y_pos, i, j, cell_state);
+            }
+        }
+    }
+
+    pub fn hash_plane(&self, time: isize) -> u64 {
+        let start = Universe::map_3d_to_1d_index(time, 0, 0);
+        let end = start + (WORLD_SIZE_X * WORLD_SIZE_Y) as usize;
+
+        self.space_time[start..end]
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| **cell == CELL_LIVE)
+            .fold(0, |hash, (index, _)| hash ^ zobrist_key(index))
+    }
+
+    pub fn count_population(&self, time: isize) -> usize {
+        let start = Universe::map_3d_to_1d_index(time, 0, 0);
+        let end = start + (WORLD_SIZE_X * WORLD_SIZE_Y) as usize;
+
+        self.space_time[start..end].iter().filter(|cell| **cell == CELL_LIVE).count()
+    }
+
+    // The latest stats report the population of the current plane
+    pub fn recount_population(&mut self) {
+        let population = self.count_population(self.current_time);
+        if let Some(stats) = self.stats.back_mut() {
+            stats.population = population;
+        }
+    }
+
+    // Edits outside the engine step change the current plane, bring what is derived from it up to date
+    pub fn refresh_current(&mut self) {
+        self.recount_population();
+
+        if let Some(ages) = &mut self.ages {
+            let plane = Universe::map_3d_to_1d_index(self.current_time, 0, 0);
+            for (index, age) in ages.iter_mut().enumerate() {
+                if self.space_time[plane + index] != CELL_LIVE {
+                    *age = 0;
+                }
+            }
+        }
+
+        // The hashes seen so far no longer lead to this plane
+        self.cycle_detector.clear();
+    }
+
+    pub fn clear_plane(&mut self, time: isize) {
+        let start = Universe::map_3d_to_1d_index(time, 0, 0);
+        let end = start + (WORLD_SIZE_X * WORLD_SIZE_Y) as usize;
+
+        self.space_time[start..end].fill(CELL_DEATH);
+    }
+
     #[inline]
     pub fn set_cell_low_level(
         &mut self,
@@ -74,18 +323,116 @@ impl Universe {
         x_index: isize,
         y_index: isize,
         cell_state: UniverseCell,
-    ) -> () {
+    ) {
         self.space_time[Universe::map_3d_to_1d_index(next_time, x_index, y_index)] = cell_state;
     }
 
     pub fn get_current_world(&self, bounds: WorldBounds) -> UniversePlane {
+        self.get_time_world(self.current_time, bounds)
+    }
+
+    // Oldest and newest generation still in the history planes
+    pub fn get_history_range(&self) -> (usize, usize) {
+        (
+            self.generation.saturating_sub(HISTORY_SIZE as usize - 1),
+            self.generation,
+        )
+    }
+
+    pub fn get_world_at(&self, generation: usize, bounds: WorldBounds) -> Option<UniversePlane> {
+        let (oldest, newest) = self.get_history_range();
+        if generation < oldest || generation > newest {
+            return None;
+        }
+
+        let back = (newest - generation) as isize;
+        let time = (self.current_time - back).rem_euclid(HISTORY_SIZE);
+
+        Some(self.get_time_world(time, bounds))
+    }
+
+    pub fn set_age_tracking(&mut self, enabled: bool) {
+        self.ages = match enabled {
+            true => Some(vec![0; (WORLD_SIZE_X * WORLD_SIZE_Y) as usize]),
+            false => None,
+        };
+    }
+
+    // Live cells of the current plane get a generation older, dead ones start over
+    pub fn update_ages(&mut self) {
+        if let Some(ages) = &mut self.ages {
+            let plane = Universe::map_3d_to_1d_index(self.current_time, 0, 0);
+            for (index, age) in ages.iter_mut().enumerate() {
+                *age = match self.space_time[plane + index] == CELL_LIVE {
+                    true => age.saturating_add(1),
+                    false => 0,
+                };
+            }
+        }
+    }
+
+    pub fn get_current_ages(&self, bounds: WorldBounds) -> Option<Vec<Vec<u32>>> {
+        let ages = self.ages.as_ref()?;
+
+        let mut region = vec![vec![0; bounds.h as usize]; bounds.w as usize];
+
+        for a in bounds.x..bounds.x + bounds.w {
+            for b in bounds.y..bounds.y + bounds.h {
+                let (al, bl) = self.position_to_index(a, b);
+                let (al, bl) = self.fix_index(al, bl);
+                region[(a - bounds.x) as usize][(b - bounds.y) as usize] =
+                    ages[Universe::map_3d_to_1d_index(0, al, bl)];
+            }
+        }
+
+        Some(region)
+    }
+
+    // State changes of every cell across the history planes, and how many steps they span
+    pub fn get_activity(&self, bounds: WorldBounds) -> (Vec<Vec<u32>>, usize) {
+        let (oldest, newest) = self.get_history_range();
+        let steps = newest - oldest;
+
+        // Plane offsets of the region cells, walked once per pair of planes
+        let mut cells: Vec<(usize, usize, usize)> = Vec::with_capacity((bounds.w * bounds.h) as usize);
+        for a in bounds.x..bounds.x + bounds.w {
+            for b in bounds.y..bounds.y + bounds.h {
+                let (al, bl) = self.position_to_index(a, b);
+                let (al, bl) = self.fix_index(al, bl);
+                cells.push((
+                    (a - bounds.x) as usize,
+                    (b - bounds.y) as usize,
+                    Universe::map_3d_to_1d_index(0, al, bl),
+                ));
+            }
+        }
+
+        let mut changes = vec![vec![0; bounds.h as usize]; bounds.w as usize];
+
+        let plane_size = (WORLD_SIZE_X * WORLD_SIZE_Y) as usize;
+        for back in 0..steps as isize {
+            let time = (self.current_time - back).rem_euclid(HISTORY_SIZE) as usize;
+            let previous = (self.current_time - back - 1).rem_euclid(HISTORY_SIZE) as usize;
+
+            for (a, b, offset) in &cells {
+                if self.space_time[time * plane_size + offset] != self.space_time[previous * plane_size + offset] {
+                    changes[*a][*b] += 1;
+                }
+            }
+        }
+
+        (changes, steps)
+    }
+
+    fn get_time_world(&self, time: isize, bounds: WorldBounds) -> UniversePlane {
         let mut world_surface = vec![vec![CELL_DEATH; bounds.h as usize]; bounds.w as usize];
 
         for a in bounds.x..bounds.x + bounds.w {
             for b in bounds.y..bounds.y + bounds.h {
                 let (al, bl) = self.position_to_index(a, b);
+                let (al, bl) = self.fix_index(al, bl);
                 world_surface[(a - bounds.x) as usize][(b - bounds.y) as usize] =
-                    self.get_current_time_cell(al, bl);
+                    self.space_time[Universe::map_3d_to_1d_index(time, al, bl)];
             }
         }
 
@@ -143,17 +490,141 @@ impl Universe {
     }
 
     #[inline]
-    pub fn set_current_time(&mut self, time: isize) -> () {
+    pub fn set_current_time(&mut self, time: isize) {
         self.current_time = time;
     }
 
     #[inline]
-    pub fn push_entity(&mut self, entity: UniversePlane) -> () {
-        self.species.push(entity);
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    #[inline]
+    pub fn next_generation(&mut self) {
+        self.generation += 1;
+    }
+
+    #[inline]
+    pub fn push_placement(&mut self, placement: Placement) {
+        self.pending_placements.push(placement);
+    }
+
+    #[inline]
+    pub fn take_placements(&mut self) -> Vec<Placement> {
+        std::mem::take(&mut self.pending_placements)
+    }
+
+    #[inline]
+    pub fn push_stats(&mut self, stats: GenerationStats) {
+        if self.stats.len() == STATS_HISTORY_SIZE {
+            self.stats.pop_front();
+        }
+        self.stats.push_back(stats);
+    }
+
+    #[inline]
+    pub fn get_stats(&self) -> &VecDeque<GenerationStats> {
+        &self.stats
+    }
+
+    #[inline]
+    pub fn observe_hash(&mut self, hash: u64) -> Option<WorldCycle> {
+        self.cycle_detector.observe(self.generation, hash)
+    }
+
+    #[inline]
+    pub fn get_cycle(&self) -> Option<WorldCycle> {
+        self.cycle_detector.get_cycle()
+    }
+
+    #[inline]
+    pub fn get_entropy_policy(&self) -> &EntropyPolicy {
+        &self.entropy_policy
+    }
+
+    #[inline]
+    pub fn set_entropy_policy(&mut self, policy: EntropyPolicy) {
+        self.entropy_policy = policy;
+    }
+
+    #[inline]
+    pub fn get_last_entropy(&self) -> usize {
+        self.last_entropy
+    }
+
+    #[inline]
+    pub fn set_last_entropy(&mut self, generation: usize) {
+        self.last_entropy = generation;
+    }
+
+    // Number the reports and keep them in the bounded event log
+    pub fn push_entropy_events(&mut self, events: Vec<InjectionReport>) -> Vec<InjectionReport> {
+        let mut logged = Vec::with_capacity(events.len());
+
+        for mut event in events {
+            event.id = self.next_event_id;
+            self.next_event_id += 1;
+
+            if self.entropy_events.len() == ENTROPY_EVENTS_SIZE {
+                self.entropy_events.pop_front();
+            }
+            self.entropy_events.push_back(event.clone());
+            logged.push(event);
+        }
+
+        logged
+    }
+
+    #[inline]
+    pub fn get_entropy_events(&self) -> &VecDeque<InjectionReport> {
+        &self.entropy_events
+    }
+
+    #[inline]
+    pub fn get_control(&self) -> EngineControl {
+        self.control
+    }
+
+    #[inline]
+    pub fn set_control(&mut self, control: EngineControl) {
+        self.control = control;
+    }
+
+    #[inline]
+    pub fn get_seeding(&self) -> &WorldSeeding {
+        &self.seeding
+    }
+
+    #[inline]
+    pub fn set_seeding(&mut self, seeding: WorldSeeding) {
+        self.seeding = seeding;
+    }
+
+    pub fn get_last_injections(&self) -> Vec<InjectionReport> {
+        match self.entropy_events.back() {
+            None => Vec::new(),
+            Some(last) => self
+                .entropy_events
+                .iter()
+                .filter(|event| event.generation == last.generation)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    #[inline]
+    pub fn set_species(&mut self, species: Vec<Species>, report: SpeciesLoadReport) {
+        self.species = species;
+        self.species_report = report;
+    }
+
+    #[inline]
+    pub fn get_species_report(&self) -> &SpeciesLoadReport {
+        &self.species_report
     }
 
     #[inline]
-    pub fn get_entities(&self) -> &UniversePlaneSet {
+    pub fn get_species(&self) -> &Vec<Species> {
         &self.species
     }
 }
@@ -1,51 +1,117 @@
+mod game_animation;
 mod game_api;
+mod game_bench;
+mod game_census;
+mod game_classifier;
+mod game_cli;
+mod game_config;
 mod game_constants;
 mod game_engine;
 mod game_entropy;
+mod game_error;
+mod game_library;
+mod game_period;
+mod game_region;
+mod game_render;
+mod game_seeding;
+mod game_soup;
 mod game_species;
+mod game_stats;
+mod game_stream;
+mod game_tiles;
 mod game_universe;
 
 use actix_files as fs;
 use actix_web::{middleware, web, App, HttpServer};
-use game_constants::UniversePlane;
+use clap::Parser;
+use rand::Rng;
 use std::{
-    sync::{mpsc::channel, Arc, RwLock},
+    sync::{Arc, RwLock},
     thread,
 };
+use tokio::sync::broadcast;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let cli = game_cli::Cli::parse();
+
+    let serve_args = match cli.command {
+        Some(game_cli::Command::Soup(args)) => {
+            return game_soup::soup_main(&args);
+        }
+        Some(game_cli::Command::Bench(args)) => {
+            return game_bench::bench_main(&args);
+        }
+        Some(game_cli::Command::Animate(args)) => {
+            return game_animation::animate_main(&args);
+        }
+        Some(game_cli::Command::Serve(args)) => args,
+        None => cli.serve,
+    };
+
+    // Load Game Config
+    let mut config = game_config::GameConfig::load();
+    if serve_args.seed.is_some() {
+        config.seed = serve_args.seed;
+    }
+    if let Some(seeding) = serve_args.seeding() {
+        match seeding.validate() {
+            Err(e) => {
+                eprintln!("Seeding: {}", e);
+            }
+            Ok(_) => config.seeding = seeding,
+        }
+    }
+
     // Load Game Shared Data
-    let mut universe = game_universe::Universe::new();
-    game_species::load_plaintext_species(&mut universe);
-    game_species::load_rle_species(&mut universe);
+    let seed = config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("Simulation seed: {}", seed);
+    let mut universe = game_universe::Universe::new(seed);
+    universe.set_entropy_policy(config.entropy.clone());
+    universe.set_seeding(config.seeding.clone());
+    universe.set_age_tracking(config.cell_ages);
     let rwlock_root = Arc::new(RwLock::new(universe));
+    game_library::load_library(&rwlock_root, &config.species_paths);
 
-    // Start Game Engine
-    let (sender, receiver) = channel::<UniversePlane>();
-
-    let rwlock_engine = Arc::clone(&rwlock_root);
-    match thread::Builder::new()
-        .name("Game Engine".into())
-        .spawn(move || {
-            game_engine::engine_loop(rwlock_engine, &receiver);
-        }) {
+    // Seed Initial World
+    match rwlock_root.write() {
         Err(e) => {
             eprintln!("{:?}", e);
         }
-        Ok(_) => {}
+        Ok(mut universe) => {
+            if let Err(e) = game_seeding::seed_world(&mut universe) {
+                eprintln!("Seeding: {}", e);
+            }
+        },
     }
 
-    let rwlock_entropy = Arc::clone(&rwlock_root);
-    match thread::Builder::new()
-        .name("Game Entropy".into())
-        .spawn(move || {
-            game_entropy::entropy_loop(rwlock_entropy, &sender);
-        }) {
-        Err(e) => {
+    // Start Species Library Watcher
+    if config.species_watch {
+        let rwlock_library = Arc::clone(&rwlock_root);
+        let species_paths = config.species_paths.clone();
+        if let Err(e) = thread::Builder::new()
+            .name("Game Library".into())
+            .spawn(move || {
+                game_library::library_loop(rwlock_library, species_paths);
+            })
+        {
             eprintln!("{:?}", e);
         }
-        Ok(_) => {}
+    }
+
+    // Start Game Engine
+    let (event_sender, _) =
+        broadcast::channel::<game_stream::EngineEvent>(game_constants::STREAM_CHANNEL_SIZE);
+    let rwlock_engine = Arc::clone(&rwlock_root);
+    let config_engine = config.clone();
+    let event_engine = event_sender.clone();
+    if let Err(e) = thread::Builder::new()
+        .name("Game Engine".into())
+        .spawn(move || {
+            game_engine::engine_loop(rwlock_engine, &config_engine, event_engine);
+        })
+    {
+        eprintln!("{:?}", e);
     }
 
     // Start API Server
@@ -55,10 +121,33 @@ async fn main() -> std::io::Result<()> {
         let rwlock_app = Arc::clone(&rwlock_root);
         App::new()
             .app_data(web::Data::new(rwlock_app))
+            .app_data(web::Data::new(event_sender.clone()))
+            .app_data(web::Data::new(config.limits))
+            .app_data(web::PathConfig::default().error_handler(|e, _| game_error::extractor_error(e)))
+            .app_data(web::QueryConfig::default().error_handler(|e, _| game_error::extractor_error(e)))
+            .app_data(web::JsonConfig::default().error_handler(|e, _| game_error::extractor_error(e)))
             .wrap(middleware::Compress::default())
             .service(game_api::gettext)
             .service(game_api::getimage)
+            .service(game_api::animation)
+            .service(game_api::tile)
             .service(game_api::getsvg)
+            .service(game_api::region)
+            .service(game_api::place)
+            .service(game_api::cells)
+            .service(game_api::species_report)
+            .service(game_api::species_index)
+            .service(game_api::census)
+            .service(game_api::stats)
+            .service(game_api::status)
+            .service(game_api::seed)
+            .service(game_api::get_entropy_policy)
+            .service(game_api::put_entropy_policy)
+            .service(game_api::events)
+            .service(game_api::ws)
+            .service(game_api::events_stream)
+            .service(game_api::get_control)
+            .service(game_api::post_control)
             .service(fs::Files::new("/", &current_path).index_file("index.html"))
     })
     .bind((game_constants::API_ADDRESS, game_constants::API_PORT))?